@@ -0,0 +1,302 @@
+// Heap allocation statistics for a single problem run.
+//
+// With the alloc-stats feature enabled, a counting global allocator tracks
+// allocations made on the current thread while measure() is running. Work
+// handed to other threads (e.g. rayon workers) is counted if it runs inside
+// Scope::enter. Without the feature, measure() returns no stats and adds no
+// overhead.
+
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AllocStats {
+    // Number of allocations, including reallocations.
+    pub allocations: u64,
+    // Total bytes requested over all allocations.
+    pub bytes: u64,
+    // Highest number of live bytes allocated during the measurement.
+    pub peak_bytes: u64,
+}
+
+impl fmt::Display for AllocStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} allocs, {} allocated, {} peak",
+            self.allocations,
+            Bytes(self.bytes),
+            Bytes(self.peak_bytes)
+        )
+    }
+}
+
+// Formats a byte count with a binary unit, e.g. "1.5 MiB".
+pub struct Bytes(pub u64);
+
+impl fmt::Display for Bytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+
+        let mut value = self.0 as f64;
+        let mut unit = 0;
+        while value >= 1024.0 && unit < UNITS.len() - 1 {
+            value /= 1024.0;
+            unit += 1;
+        }
+
+        let s = if unit == 0 {
+            format!("{} B", self.0)
+        } else {
+            format!("{:.1} {}", value, UNITS[unit])
+        };
+        f.pad(&s)
+    }
+}
+
+// Runs f and returns the allocations it made on this thread.
+#[cfg(feature = "alloc-stats")]
+pub fn measure<R>(f: impl FnOnce() -> R) -> (R, Option<AllocStats>) {
+    let counters = std::sync::Arc::default();
+    let ret = counting::enter(Some(&counters), f);
+    (ret, Some(counters.stats()))
+}
+
+#[cfg(not(feature = "alloc-stats"))]
+pub fn measure<R>(f: impl FnOnce() -> R) -> (R, Option<AllocStats>) {
+    (f(), None)
+}
+
+// The measurement running on a thread, to carry over to worker threads whose
+// allocations belong to it. A scope keeps its counters alive, so entering it
+// after its measurement has finished is harmless; the counts are just lost.
+#[derive(Clone, Debug, Default)]
+pub struct Scope {
+    // None if no measurement was running.
+    #[cfg(feature = "alloc-stats")]
+    counters: Option<std::sync::Arc<counting::Counters>>,
+}
+
+impl Scope {
+    pub fn current() -> Self {
+        Self {
+            #[cfg(feature = "alloc-stats")]
+            counters: counting::current(),
+        }
+    }
+
+    // Runs f with its allocations counted towards this scope.
+    pub fn enter<R>(&self, f: impl FnOnce() -> R) -> R {
+        #[cfg(feature = "alloc-stats")]
+        return counting::enter(self.counters.as_ref(), f);
+        #[cfg(not(feature = "alloc-stats"))]
+        f()
+    }
+}
+
+#[cfg(feature = "alloc-stats")]
+mod counting {
+    use std::{
+        alloc::{GlobalAlloc, Layout, System},
+        cell::Cell,
+        ptr,
+        sync::{
+            Arc,
+            atomic::{AtomicI64, AtomicU64, Ordering},
+        },
+    };
+
+    use super::AllocStats;
+
+    struct CountingAlloc;
+
+    #[global_allocator]
+    static GLOBAL: CountingAlloc = CountingAlloc;
+
+    // Shared by every thread taking part in a measurement.
+    #[derive(Debug, Default)]
+    pub(super) struct Counters {
+        allocations: AtomicU64,
+        bytes: AtomicU64,
+        // Signed because memory allocated before the measurement may be
+        // freed during it.
+        live: AtomicI64,
+        peak: AtomicI64,
+    }
+
+    impl Counters {
+        pub(super) fn stats(&self) -> AllocStats {
+            AllocStats {
+                allocations: self.allocations.load(Ordering::Relaxed),
+                bytes: self.bytes.load(Ordering::Relaxed),
+                peak_bytes: self.peak.load(Ordering::Relaxed).max(0) as u64,
+            }
+        }
+    }
+
+    thread_local! {
+        // The measurement this thread's allocations count towards, or null.
+        // Must not allocate or need a destructor since it is used from
+        // inside the allocator. A pointer is only installed by enter, whose
+        // caller holds an Arc to it until it is uninstalled.
+        static CURRENT: Cell<*const Counters> = const { Cell::new(ptr::null()) };
+    }
+
+    fn record(allocated: usize, freed: usize) {
+        let _ = CURRENT.try_with(|c| {
+            // Safety: see CURRENT.
+            let Some(counters) = (unsafe { c.get().as_ref() }) else {
+                return;
+            };
+            if allocated > 0 {
+                counters.allocations.fetch_add(1, Ordering::Relaxed);
+                counters
+                    .bytes
+                    .fetch_add(allocated as u64, Ordering::Relaxed);
+            }
+            let delta = allocated as i64 - freed as i64;
+            let live = counters.live.fetch_add(delta, Ordering::Relaxed) + delta;
+            counters.peak.fetch_max(live, Ordering::Relaxed);
+        });
+    }
+
+    pub(super) fn current() -> Option<Arc<Counters>> {
+        let p = CURRENT.with(|c| c.get());
+        if p.is_null() {
+            return None;
+        }
+        // Safety: see CURRENT. The new reference is balanced by from_raw.
+        unsafe {
+            Arc::increment_strong_count(p);
+            Some(Arc::from_raw(p))
+        }
+    }
+
+    // Runs f with this thread's allocations counted towards counters, or not
+    // counted at all if None. The previous measurement is restored
+    // afterwards, even if f panics, so measurements can nest.
+    pub(super) fn enter<R>(counters: Option<&Arc<Counters>>, f: impl FnOnce() -> R) -> R {
+        struct Restore(*const Counters);
+
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                CURRENT.with(|c| c.set(self.0));
+            }
+        }
+
+        let counters = counters.map_or(ptr::null(), Arc::as_ptr);
+        let _restore = Restore(CURRENT.with(|c| c.replace(counters)));
+        f()
+    }
+
+    unsafe impl GlobalAlloc for CountingAlloc {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let ret = unsafe { System.alloc(layout) };
+            if !ret.is_null() {
+                record(layout.size(), 0);
+            }
+            ret
+        }
+
+        unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+            let ret = unsafe { System.alloc_zeroed(layout) };
+            if !ret.is_null() {
+                record(layout.size(), 0);
+            }
+            ret
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            unsafe { System.dealloc(ptr, layout) };
+            record(0, layout.size());
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            let ret = unsafe { System.realloc(ptr, layout, new_size) };
+            if !ret.is_null() {
+                record(new_size, layout.size());
+            }
+            ret
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bytes_display_test() {
+        assert_eq!(Bytes(512).to_string(), "512 B");
+        assert_eq!(Bytes(1536).to_string(), "1.5 KiB");
+        assert_eq!(Bytes(3 * 1024 * 1024).to_string(), "3.0 MiB");
+        assert_eq!(format!("{:>8}", Bytes(10)), "    10 B");
+    }
+
+    #[cfg(feature = "alloc-stats")]
+    #[test]
+    fn measure_test() {
+        let (_, stats) = measure(|| {
+            let a = vec![0u8; 1000];
+            let b = vec![0u8; 500];
+            drop(a);
+            drop(b);
+            let c: Vec<u8> = Vec::with_capacity(100);
+            std::hint::black_box(c);
+        });
+
+        let stats = stats.unwrap();
+        assert_eq!(stats.allocations, 3);
+        assert_eq!(stats.bytes, 1600);
+        assert_eq!(stats.peak_bytes, 1500);
+    }
+
+    #[cfg(feature = "alloc-stats")]
+    #[test]
+    fn measure_threads_test() {
+        let (_, stats) = measure(|| {
+            let scope = Scope::current();
+            // Spawning allocates on this thread, so keep that out of the
+            // measurement.
+            Scope::default().enter(|| {
+                std::thread::scope(|s| {
+                    for _ in 0..4 {
+                        s.spawn(|| {
+                            // Not counted.
+                            std::hint::black_box(vec![0u8; 10]);
+                            scope.enter(|| std::hint::black_box(vec![0u8; 100]));
+                        });
+                    }
+                })
+            });
+        });
+
+        let stats = stats.unwrap();
+        assert_eq!(stats.allocations, 4);
+        assert_eq!(stats.bytes, 400);
+    }
+
+    #[cfg(feature = "alloc-stats")]
+    #[test]
+    fn enter_panic_test() {
+        use std::panic::{self, AssertUnwindSafe};
+
+        // A scope that outlives its measurement.
+        let (scope, _) = measure(Scope::current);
+
+        let (_, stats) = measure(|| {
+            let res = panic::catch_unwind(AssertUnwindSafe(|| {
+                scope.enter(|| panic::resume_unwind(Box::new(())))
+            }));
+            assert!(res.is_err());
+            // Counted by this measurement again.
+            std::hint::black_box(vec![0u8; 100]);
+        });
+        let stats = stats.unwrap();
+        assert_eq!((stats.allocations, stats.bytes), (1, 100));
+
+        let counters = scope.counters.as_ref().unwrap();
+        let before = counters.stats().bytes;
+        scope.enter(|| std::hint::black_box(vec![0u8; 10]));
+        assert_eq!(counters.stats().bytes, before + 10);
+    }
+}
@@ -0,0 +1,301 @@
+pub mod history;
+#[cfg(test)]
+mod test_server;
+
+pub use history::{Attempt, Check, History, Outcome};
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+
+pub const DEFAULT_BASE_URL: &str = "https://adventofcode.com";
+pub const YEAR: u32 = 2024;
+
+const USER_AGENT: &str = "github.com/stephenmw/adventofcode2024 by Stephen Weinberg";
+
+// Minimum time between two requests to the server. The time of the last
+// request is stored on disk so the limit also holds across invocations.
+const MIN_REQUEST_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub base_url: String,
+    pub year: u32,
+    pub session: Option<String>,
+    // Directory the puzzle inputs are cached in.
+    pub input_dir: PathBuf,
+    // Directory for local state such as the rate limit timestamp.
+    pub state_dir: PathBuf,
+    pub min_request_interval: Duration,
+}
+
+impl Config {
+    // Builds the default config. If session is None, it is read from
+    // .aoc/session if that file exists.
+    pub fn new(base_url: String, session: Option<String>) -> Result<Self> {
+        let state_dir = PathBuf::from(".aoc");
+        let session = match session {
+            Some(s) => Some(s),
+            None => read_session_file(&state_dir.join("session"))?,
+        };
+
+        Ok(Self {
+            base_url: base_url.trim_end_matches('/').to_owned(),
+            year: YEAR,
+            session,
+            input_dir: PathBuf::from("puzzle-inputs"),
+            state_dir,
+            min_request_interval: MIN_REQUEST_INTERVAL,
+        })
+    }
+}
+
+fn read_session_file(path: &Path) -> Result<Option<String>> {
+    match fs::read_to_string(path) {
+        Ok(s) => Ok(Some(s.trim().to_owned())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("failed to read {}", path.display())),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Fetched {
+    Cached(PathBuf),
+    Downloaded(PathBuf),
+}
+
+pub struct Client {
+    config: Config,
+    agent: ureq::Agent,
+}
+
+impl Client {
+    pub fn new(config: Config) -> Self {
+        let agent = ureq::Agent::config_builder()
+            .http_status_as_error(false)
+            .timeout_global(Some(Duration::from_secs(30)))
+            .user_agent(USER_AGENT)
+            .build()
+            .into();
+
+        Self { config, agent }
+    }
+
+    pub fn input_path(&self, day: usize) -> PathBuf {
+        self.config.input_dir.join(format!("day{:02}.txt", day))
+    }
+
+    // Downloads the input for day unless it is already cached on disk.
+    pub fn fetch_input(&self, day: usize) -> Result<Fetched> {
+        let path = self.input_path(day);
+        if path.exists() {
+            return Ok(Fetched::Cached(path));
+        }
+
+        let url = format!(
+            "{}/{}/day/{}/input",
+            self.config.base_url, self.config.year, day
+        );
+        let request = self.agent.get(&url).header("Cookie", self.cookie()?);
+
+        self.wait_for_rate_limit()?;
+        let (status, body) = read_response(request.call())?;
+
+        match status {
+            200 => (),
+            404 => bail!("input for day {} is not available", day),
+            400 | 500 => bail!("server rejected the session cookie: {}", body.trim()),
+            status => bail!("unexpected status {}: {}", status, body.trim()),
+        }
+
+        fs::create_dir_all(&self.config.input_dir).context("failed to create input directory")?;
+        // Write to a temporary file first so an interrupted download is never
+        // mistaken for a cached input.
+        let tmp_path = path.with_extension("txt.tmp");
+        fs::write(&tmp_path, body).context("failed to write input")?;
+        fs::rename(&tmp_path, &path).context("failed to write input")?;
+
+        Ok(Fetched::Downloaded(path))
+    }
+
+    // Posts an answer and returns the server's verdict. Callers should check
+    // the answer against the history first.
+    pub fn submit_answer(&self, day: usize, part: usize, answer: &str) -> Result<Outcome> {
+        let url = format!(
+            "{}/{}/day/{}/answer",
+            self.config.base_url, self.config.year, day
+        );
+        let request = self.agent.post(&url).header("Cookie", self.cookie()?);
+
+        self.wait_for_rate_limit()?;
+        let level = part.to_string();
+        let (status, body) =
+            read_response(request.send_form([("level", level.as_str()), ("answer", answer)]))?;
+
+        if status != 200 {
+            bail!("unexpected status {}: {}", status, body.trim());
+        }
+
+        Ok(Outcome::parse(&body))
+    }
+
+    pub fn history(&self) -> Result<History> {
+        History::load(self.config.state_dir.join("answers"))
+    }
+
+    fn cookie(&self) -> Result<String> {
+        let session = self.config.session.as_ref().ok_or(anyhow!(
+            "no session cookie: set AOC_SESSION or write it to {}",
+            self.config.state_dir.join("session").display()
+        ))?;
+        Ok(format!("session={}", session))
+    }
+
+    // Sleeps until at least min_request_interval has passed since the last
+    // request and then records the current time as the last request.
+    fn wait_for_rate_limit(&self) -> Result<()> {
+        let path = self.config.state_dir.join("last-request");
+        let last = fs::read_to_string(&path)
+            .ok()
+            .and_then(|x| x.trim().parse::<u64>().ok())
+            .map(|millis| UNIX_EPOCH + Duration::from_millis(millis));
+
+        if let Some(last) = last {
+            let elapsed = SystemTime::now()
+                .duration_since(last)
+                .unwrap_or(Duration::ZERO);
+            if let Some(remaining) = self.config.min_request_interval.checked_sub(elapsed) {
+                thread::sleep(remaining);
+            }
+        }
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+        fs::create_dir_all(&self.config.state_dir).context("failed to create state directory")?;
+        fs::write(&path, now.to_string()).context("failed to record request time")?;
+
+        Ok(())
+    }
+}
+
+fn read_response(
+    resp: Result<ureq::http::Response<ureq::Body>, ureq::Error>,
+) -> Result<(u16, String)> {
+    let mut resp = resp.context("request failed")?;
+    let body = resp
+        .body_mut()
+        .read_to_string()
+        .context("failed to read response")?;
+    Ok((resp.status().as_u16(), body))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::test_server::{TestDir, TestServer};
+    use super::*;
+
+    fn test_config(server: &TestServer, dir: &TestDir) -> Config {
+        Config {
+            base_url: server.base_url.clone(),
+            year: 2024,
+            session: Some("abc123".to_owned()),
+            input_dir: dir.path.join("inputs"),
+            state_dir: dir.path.join("state"),
+            min_request_interval: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn fetch_input_test() {
+        let server = TestServer::start(|_| (200, "1 2\n3 4\n".to_owned()));
+        let dir = TestDir::new("fetch_input");
+        let client = Client::new(test_config(&server, &dir));
+
+        let path = dir.path.join("inputs/day05.txt");
+        assert_eq!(
+            client.fetch_input(5).unwrap(),
+            Fetched::Downloaded(path.clone())
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), "1 2\n3 4\n");
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "GET");
+        assert_eq!(requests[0].path, "/2024/day/5/input");
+        assert_eq!(requests[0].header("cookie"), Some("session=abc123"));
+        assert_eq!(requests[0].body, "");
+    }
+
+    #[test]
+    fn fetch_input_cached_test() {
+        let server = TestServer::start(|_| (200, "new".to_owned()));
+        let dir = TestDir::new("fetch_input_cached");
+        let client = Client::new(test_config(&server, &dir));
+
+        fs::create_dir_all(dir.path.join("inputs")).unwrap();
+        let path = dir.path.join("inputs/day01.txt");
+        fs::write(&path, "old").unwrap();
+
+        assert_eq!(
+            client.fetch_input(1).unwrap(),
+            Fetched::Cached(path.clone())
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+        assert!(server.requests().is_empty());
+    }
+
+    #[test]
+    fn fetch_input_error_test() {
+        let server = TestServer::start(|_| (404, "Not Found".to_owned()));
+        let dir = TestDir::new("fetch_input_error");
+        let client = Client::new(test_config(&server, &dir));
+
+        assert!(client.fetch_input(25).is_err());
+        assert!(!dir.path.join("inputs/day25.txt").exists());
+    }
+
+    #[test]
+    fn submit_answer_test() {
+        let server = TestServer::start(|req| {
+            let body = if req.body.contains("answer=42") {
+                "<article><p>That's the right answer!</p></article>"
+            } else {
+                "<article><p>That's not the right answer; your answer is too low.</p></article>"
+            };
+            (200, body.to_owned())
+        });
+        let dir = TestDir::new("submit_answer");
+        let client = Client::new(test_config(&server, &dir));
+
+        assert_eq!(client.submit_answer(7, 2, "41").unwrap(), Outcome::TooLow);
+        assert_eq!(client.submit_answer(7, 2, "42").unwrap(), Outcome::Correct);
+
+        let requests = server.requests();
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].path, "/2024/day/7/answer");
+        assert_eq!(requests[0].body, "level=2&answer=41");
+    }
+
+    #[test]
+    fn rate_limit_test() {
+        let server = TestServer::start(|_| (200, "input".to_owned()));
+        let dir = TestDir::new("rate_limit");
+        let config = Config {
+            min_request_interval: Duration::from_millis(300),
+            ..test_config(&server, &dir)
+        };
+        let client = Client::new(config);
+
+        let start = Instant::now();
+        client.fetch_input(1).unwrap();
+        client.fetch_input(2).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(300));
+        assert_eq!(server.requests().len(), 2);
+    }
+}
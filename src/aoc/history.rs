@@ -0,0 +1,394 @@
+use std::{
+    fmt,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    str::FromStr,
+    time::Duration,
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+
+// The server's verdict on a submitted answer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Correct,
+    TooHigh,
+    TooLow,
+    Wrong,
+    // Rate limited. The answer was not checked.
+    Wait(Duration),
+    // The part is already solved or not yet unlocked.
+    WrongLevel,
+    Unknown,
+}
+
+impl Outcome {
+    // Parses the article of the page returned after POSTing an answer.
+    pub fn parse(body: &str) -> Self {
+        if body.contains("That's the right answer") {
+            Outcome::Correct
+        } else if body.contains("That's not the right answer") {
+            if body.contains("too high") {
+                Outcome::TooHigh
+            } else if body.contains("too low") {
+                Outcome::TooLow
+            } else {
+                Outcome::Wrong
+            }
+        } else if body.contains("You gave an answer too recently") {
+            Outcome::Wait(parse_wait(body).unwrap_or(Duration::ZERO))
+        } else if body.contains("You don't seem to be solving the right level") {
+            Outcome::WrongLevel
+        } else {
+            Outcome::Unknown
+        }
+    }
+
+    pub fn is_wrong(&self) -> bool {
+        matches!(self, Outcome::TooHigh | Outcome::TooLow | Outcome::Wrong)
+    }
+}
+
+// Parses "You have 1m 5s left to wait" into a duration.
+fn parse_wait(body: &str) -> Option<Duration> {
+    let start = body.find("You have ")? + "You have ".len();
+    let end = start + body[start..].find(" left to wait")?;
+
+    let mut secs = 0;
+    for part in body[start..end].split_whitespace() {
+        let (n, unit) = part.split_at(part.find(|c: char| !c.is_ascii_digit())?);
+        let n: u64 = n.parse().ok()?;
+        secs += match unit {
+            "h" => n * 3600,
+            "m" => n * 60,
+            "s" => n,
+            _ => return None,
+        };
+    }
+
+    Some(Duration::from_secs(secs))
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Correct => write!(f, "right answer"),
+            Outcome::TooHigh => write!(f, "wrong answer (too high)"),
+            Outcome::TooLow => write!(f, "wrong answer (too low)"),
+            Outcome::Wrong => write!(f, "wrong answer"),
+            Outcome::Wait(d) => write!(f, "answered too recently, wait {}s", d.as_secs()),
+            Outcome::WrongLevel => write!(f, "part already solved or not unlocked"),
+            Outcome::Unknown => write!(f, "unrecognized response"),
+        }
+    }
+}
+
+// The on-disk form used in the history file.
+impl FromStr for Outcome {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let ret = match s {
+            "correct" => Outcome::Correct,
+            "too-high" => Outcome::TooHigh,
+            "too-low" => Outcome::TooLow,
+            "wrong" => Outcome::Wrong,
+            "wrong-level" => Outcome::WrongLevel,
+            "unknown" => Outcome::Unknown,
+            _ => {
+                let secs = s
+                    .strip_prefix("wait:")
+                    .and_then(|x| x.parse().ok())
+                    .ok_or(anyhow!("unknown outcome: {}", s))?;
+                Outcome::Wait(Duration::from_secs(secs))
+            }
+        };
+
+        Ok(ret)
+    }
+}
+
+impl Outcome {
+    fn code(&self) -> String {
+        match self {
+            Outcome::Correct => "correct".to_owned(),
+            Outcome::TooHigh => "too-high".to_owned(),
+            Outcome::TooLow => "too-low".to_owned(),
+            Outcome::Wrong => "wrong".to_owned(),
+            Outcome::Wait(d) => format!("wait:{}", d.as_secs()),
+            Outcome::WrongLevel => "wrong-level".to_owned(),
+            Outcome::Unknown => "unknown".to_owned(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Attempt {
+    pub day: usize,
+    pub part: usize,
+    pub answer: String,
+    pub outcome: Outcome,
+}
+
+// What the history says about an answer before it is submitted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Check {
+    // The answer is already known to be correct; there is nothing to submit.
+    AlreadyCorrect,
+    // The answer may be submitted. Contains warnings to show the user.
+    Submit(Vec<String>),
+}
+
+// Every answer ever submitted, stored one attempt per line as
+// "day\tpart\toutcome\tanswer".
+pub struct History {
+    path: PathBuf,
+    attempts: Vec<Attempt>,
+}
+
+impl History {
+    pub fn load(path: PathBuf) -> Result<Self> {
+        let data = match fs::read_to_string(&path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e).with_context(|| format!("failed to read {}", path.display())),
+        };
+
+        let attempts = data
+            .lines()
+            .enumerate()
+            .map(|(i, line)| {
+                parse_attempt(line).with_context(|| format!("{}:{}", path.display(), i + 1))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self { path, attempts })
+    }
+
+    pub fn attempts(&self, day: usize, part: usize) -> impl Iterator<Item = &Attempt> {
+        self.attempts
+            .iter()
+            .filter(move |a| a.day == day && a.part == part)
+    }
+
+    // Refuses answers that are known to be wrong and warns about answers
+    // outside the bounds given by earlier "too high"/"too low" responses.
+    pub fn check(&self, day: usize, part: usize, answer: &str) -> Result<Check> {
+        validate_answer(answer)?;
+
+        if let Some(a) = self
+            .attempts(day, part)
+            .find(|a| a.outcome.is_wrong() && a.answer == answer)
+        {
+            bail!("{} was already submitted: {}", answer, a.outcome);
+        }
+
+        if let Some(a) = self
+            .attempts(day, part)
+            .find(|a| a.outcome == Outcome::Correct)
+        {
+            if a.answer == answer {
+                return Ok(Check::AlreadyCorrect);
+            }
+            bail!("already solved with a different answer: {}", a.answer);
+        }
+
+        let mut warnings = Vec::new();
+        if let Ok(n) = answer.parse::<i128>() {
+            let bound = |outcome| {
+                self.attempts(day, part)
+                    .filter(move |a| a.outcome == outcome)
+                    .filter_map(|a| a.answer.parse::<i128>().ok())
+            };
+
+            if let Some(high) = bound(Outcome::TooHigh).min().filter(|&x| n >= x) {
+                warnings.push(format!("{} is not below {}, which was too high", n, high));
+            }
+            if let Some(low) = bound(Outcome::TooLow).max().filter(|&x| n <= x) {
+                warnings.push(format!("{} is not above {}, which was too low", n, low));
+            }
+        }
+
+        Ok(Check::Submit(warnings))
+    }
+
+    pub fn record(&mut self, attempt: Attempt) -> Result<()> {
+        validate_answer(&attempt.answer)?;
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).context("failed to create state directory")?;
+        }
+
+        let mut f = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("failed to open {}", self.path.display()))?;
+        writeln!(
+            f,
+            "{}\t{}\t{}\t{}",
+            attempt.day,
+            attempt.part,
+            attempt.outcome.code(),
+            attempt.answer
+        )
+        .context("failed to record attempt")?;
+
+        self.attempts.push(attempt);
+        Ok(())
+    }
+}
+
+// Answers are stored unescaped, so they can't contain the field or line
+// separators.
+fn validate_answer(answer: &str) -> Result<()> {
+    if answer.contains(['\t', '\n', '\r']) {
+        bail!("answer contains a tab or line break: {:?}", answer);
+    }
+    Ok(())
+}
+
+fn parse_attempt(line: &str) -> Result<Attempt> {
+    let mut fields = line.splitn(4, '\t');
+    let mut next = || fields.next().ok_or(anyhow!("missing field"));
+
+    Ok(Attempt {
+        day: next()?.parse()?,
+        part: next()?.parse()?,
+        outcome: next()?.parse()?,
+        answer: next()?.to_owned(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_server::TestDir;
+    use super::*;
+
+    #[test]
+    fn outcome_parse_test() {
+        let cases = [
+            (
+                "<p>That's the right answer! You are one gold star closer.</p>",
+                Outcome::Correct,
+            ),
+            (
+                "<p>That's not the right answer; your answer is too high. Please wait one minute before trying again.</p>",
+                Outcome::TooHigh,
+            ),
+            (
+                "<p>That's not the right answer; your answer is too low.</p>",
+                Outcome::TooLow,
+            ),
+            (
+                "<p>That's not the right answer. If you're stuck...</p>",
+                Outcome::Wrong,
+            ),
+            (
+                "<p>You gave an answer too recently; you have to wait after submitting an answer before trying again.  You have 1m 5s left to wait.</p>",
+                Outcome::Wait(Duration::from_secs(65)),
+            ),
+            (
+                "<p>You don't seem to be solving the right level.  Did you already complete it?</p>",
+                Outcome::WrongLevel,
+            ),
+            ("<html>maintenance</html>", Outcome::Unknown),
+        ];
+
+        for (body, expected) in cases {
+            assert_eq!(Outcome::parse(body), expected, "{}", body);
+        }
+    }
+
+    #[test]
+    fn outcome_code_roundtrip_test() {
+        let outcomes = [
+            Outcome::Correct,
+            Outcome::TooHigh,
+            Outcome::TooLow,
+            Outcome::Wrong,
+            Outcome::Wait(Duration::from_secs(42)),
+            Outcome::WrongLevel,
+            Outcome::Unknown,
+        ];
+
+        for o in outcomes {
+            assert_eq!(o.code().parse::<Outcome>().unwrap(), o);
+        }
+    }
+
+    fn warnings(check: Check) -> Vec<String> {
+        match check {
+            Check::AlreadyCorrect => vec![],
+            Check::Submit(w) => w,
+        }
+    }
+
+    #[test]
+    fn history_check_test() {
+        let dir = TestDir::new("history_check");
+        let path = dir.path.join("answers");
+
+        let mut history = History::load(path.clone()).unwrap();
+        for (answer, outcome) in [("100", Outcome::TooHigh), ("10", Outcome::TooLow)] {
+            let answer = answer.to_owned();
+            history
+                .record(Attempt {
+                    day: 3,
+                    part: 1,
+                    answer,
+                    outcome,
+                })
+                .unwrap();
+        }
+
+        // Reload to make sure the file roundtrips.
+        let history = History::load(path).unwrap();
+
+        assert!(history.check(3, 1, "100").is_err());
+        assert!(history.check(3, 1, "10").is_err());
+        assert_eq!(history.check(3, 1, "50").unwrap(), Check::Submit(vec![]));
+        assert_eq!(warnings(history.check(3, 1, "150").unwrap()).len(), 1);
+        assert_eq!(warnings(history.check(3, 1, "5").unwrap()).len(), 1);
+
+        // Other parts are unaffected.
+        assert_eq!(history.check(3, 2, "100").unwrap(), Check::Submit(vec![]));
+    }
+
+    #[test]
+    fn history_check_correct_test() {
+        let dir = TestDir::new("history_check_correct");
+        let mut history = History::load(dir.path.join("answers")).unwrap();
+        history
+            .record(Attempt {
+                day: 1,
+                part: 2,
+                answer: "abc".to_owned(),
+                outcome: Outcome::Correct,
+            })
+            .unwrap();
+
+        assert_eq!(history.check(1, 2, "abc").unwrap(), Check::AlreadyCorrect);
+        assert!(history.check(1, 2, "abd").is_err());
+    }
+
+    #[test]
+    fn history_separator_test() {
+        let dir = TestDir::new("history_separator");
+        let path = dir.path.join("answers");
+        let mut history = History::load(path.clone()).unwrap();
+
+        for answer in ["12\t3", "12\n3", "12\r"] {
+            assert!(history.check(1, 1, answer).is_err(), "{:?}", answer);
+            let attempt = Attempt {
+                day: 1,
+                part: 1,
+                answer: answer.to_owned(),
+                outcome: Outcome::Wrong,
+            };
+            assert!(history.record(attempt).is_err(), "{:?}", answer);
+        }
+        assert_eq!(History::load(path).unwrap().attempts(1, 1).count(), 0);
+    }
+}
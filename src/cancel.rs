@@ -0,0 +1,164 @@
+// Cooperative cancellation for long running solutions.
+//
+// run_with_timeout runs a function on a worker thread with a CancelToken
+// installed. Solutions call check() (or is_cancelled()) in their hot loops
+// and stop early once the budget is exceeded. Solutions that never check are
+// abandoned: their thread keeps running until the process exits, but the
+// caller gets a TimedOut error and moves on.
+
+use std::{
+    cell::RefCell,
+    fmt,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, RecvTimeoutError},
+    },
+    thread,
+    time::Duration,
+};
+
+use anyhow::{Result, anyhow};
+
+// How long a cancelled worker is given to return before it is abandoned.
+const CANCEL_GRACE: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<CancelToken>> = const { RefCell::new(None) };
+}
+
+// Runs f with token installed as the current thread's token.
+pub fn with_token<R>(token: CancelToken, f: impl FnOnce() -> R) -> R {
+    let prev = CURRENT.with(|c| c.replace(Some(token)));
+    let ret = f();
+    CURRENT.with(|c| *c.borrow_mut() = prev);
+    ret
+}
+
+// The current thread's token, to install on worker threads (e.g. rayon)
+// that should stop with this one.
+pub fn current() -> Option<CancelToken> {
+    CURRENT.with(|c| c.borrow().clone())
+}
+
+// Returns true if the current thread's token was cancelled. Always false
+// when no token is installed.
+pub fn is_cancelled() -> bool {
+    CURRENT.with(|c| c.borrow().as_ref().is_some_and(|t| t.is_cancelled()))
+}
+
+pub fn check() -> Result<(), Cancelled> {
+    if is_cancelled() {
+        Err(Cancelled)
+    } else {
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimedOut(pub Duration);
+
+impl fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "timed out after {:?}", self.0)
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+// Runs f on a new thread and waits at most timeout for it to finish.
+pub fn run_with_timeout<T, F>(timeout: Duration, f: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T> + Send + 'static,
+{
+    let token = CancelToken::new();
+    let (tx, rx) = mpsc::channel();
+
+    let worker_token = token.clone();
+    thread::Builder::new().spawn(move || {
+        // The receiver is gone if the worker was abandoned.
+        let _ = tx.send(with_token(worker_token, f));
+    })?;
+
+    match rx.recv_timeout(timeout) {
+        Ok(res) => res,
+        Err(RecvTimeoutError::Timeout) => {
+            token.cancel();
+            let _ = rx.recv_timeout(CANCEL_GRACE);
+            Err(TimedOut(timeout).into())
+        }
+        Err(RecvTimeoutError::Disconnected) => {
+            Err(anyhow!("worker thread exited without a result"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_with_timeout_ok_test() {
+        let res = run_with_timeout(Duration::from_secs(10), || Ok(42));
+        assert_eq!(res.unwrap(), 42);
+    }
+
+    #[test]
+    fn run_with_timeout_cooperative_test() {
+        let res: Result<()> = run_with_timeout(Duration::from_millis(50), || {
+            loop {
+                check()?;
+                thread::sleep(Duration::from_millis(1));
+            }
+        });
+        assert!(res.unwrap_err().is::<TimedOut>());
+    }
+
+    #[test]
+    fn run_with_timeout_abandon_test() {
+        let res: Result<()> = run_with_timeout(Duration::from_millis(50), || {
+            thread::sleep(Duration::from_secs(60));
+            Ok(())
+        });
+        assert!(res.unwrap_err().is::<TimedOut>());
+    }
+
+    #[test]
+    fn no_token_test() {
+        assert!(!is_cancelled());
+
+        let token = CancelToken::new();
+        token.cancel();
+        assert_eq!(with_token(token, check), Err(Cancelled));
+        assert!(!is_cancelled());
+    }
+}
@@ -1,21 +1,164 @@
 #![allow(dead_code)]
 
+pub mod bits;
+pub mod components;
+pub mod distance;
+pub mod image;
+pub mod obstacles;
+pub mod render;
+pub mod sparse;
+pub mod view;
+pub use render::Render;
+pub use view::SubGrid;
+
+use std::{
+    fmt,
+    ops::{Add, AddAssign, Index, IndexMut, Mul, Neg, Sub, SubAssign},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GridError {
+    Empty,
+    // Row y has a different number of cells than row 0.
+    Ragged {
+        row: usize,
+        expected: usize,
+        actual: usize,
+    },
+}
+
+impl fmt::Display for GridError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GridError::Empty => write!(f, "grid has no cells"),
+            GridError::Ragged {
+                row,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "row {} has {} cells, expected {} like row 0",
+                row, actual, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GridError {}
+
+// How movement behaves at the edges of a grid.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Topology {
+    // Moving off an edge leaves the grid.
+    #[default]
+    Bounded,
+    // Moving off an edge wraps around to the opposite edge.
+    Toroidal,
+}
+
+// A rectangular grid stored row-major in a single Vec. Point (0, 0) is the
+// first cell of the first row.
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Grid<T> {
-    pub cells: Vec<Vec<T>>,
+    cells: Vec<T>,
+    width: usize,
+    height: usize,
+    topology: Topology,
 }
 
 impl<T> Grid<T> {
+    // Builds a grid from rows. Panics if try_new would fail.
     pub fn new(data: Vec<Vec<T>>) -> Self {
-        Grid { cells: data }
+        Self::try_new(data).unwrap_or_else(|e| panic!("invalid grid: {}", e))
+    }
+
+    // Builds a grid from rows. The rows must be non-empty and all the same
+    // length.
+    pub fn try_new(data: Vec<Vec<T>>) -> Result<Self, GridError> {
+        let height = data.len();
+        let width = data.first().map_or(0, |row| row.len());
+        if width == 0 {
+            return Err(GridError::Empty);
+        }
+
+        if let Some((y, row)) = data.iter().enumerate().find(|(_, r)| r.len() != width) {
+            return Err(GridError::Ragged {
+                row: y,
+                expected: width,
+                actual: row.len(),
+            });
+        }
+
+        let mut cells = Vec::with_capacity(width * height);
+        for row in data {
+            cells.extend(row);
+        }
+
+        Ok(Grid {
+            cells,
+            width,
+            height,
+            topology: Topology::Bounded,
+        })
     }
 
     pub fn get(&self, p: Point) -> Option<&T> {
-        self.cells.get(p.y)?.get(p.x)
+        let i = self.index_of(p)?;
+        Some(&self.cells[i])
     }
 
     pub fn get_mut(&mut self, p: Point) -> Option<&mut T> {
-        self.cells.get_mut(p.y)?.get_mut(p.x)
+        let i = self.index_of(p)?;
+        Some(&mut self.cells[i])
+    }
+
+    // Returns the position of p in the row-major cell slice.
+    pub fn index_of(&self, p: Point) -> Option<usize> {
+        if self.contains(p) {
+            Some(p.y * self.width + p.x)
+        } else {
+            None
+        }
+    }
+
+    // Inverse of index_of.
+    pub fn point_at(&self, i: usize) -> Point {
+        Point::new(i % self.width, i / self.width)
+    }
+
+    // All cells in row-major order.
+    pub fn cells(&self) -> &[T] {
+        &self.cells
+    }
+
+    pub fn cells_mut(&mut self) -> &mut [T] {
+        &mut self.cells
+    }
+
+    pub fn row(&self, y: usize) -> Option<&[T]> {
+        let start = self.index_of(Point::new(0, y))?;
+        Some(&self.cells[start..start + self.width])
+    }
+
+    pub fn row_mut(&mut self, y: usize) -> Option<&mut [T]> {
+        let start = self.index_of(Point::new(0, y))?;
+        Some(&mut self.cells[start..start + self.width])
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> {
+        // chunks panics on a zero chunk size; an empty grid has no rows.
+        self.cells.chunks(self.width.max(1))
+    }
+
+    // Columns are not contiguous, so this returns an iterator instead of a
+    // slice. Empty if x is out of bounds.
+    pub fn column(&self, x: usize) -> impl Iterator<Item = &T> {
+        let cells = if x < self.width {
+            &self.cells[x..]
+        } else {
+            &[]
+        };
+        cells.iter().step_by(self.width.max(1))
     }
 
     pub fn iter_points(&self) -> impl Iterator<Item = Point> {
@@ -24,24 +167,183 @@ impl<T> Grid<T> {
     }
 
     pub fn iter_items(&self) -> impl Iterator<Item = (Point, &T)> {
-        self.cells.iter().enumerate().flat_map(move |(y, row)| {
-            row.iter()
-                .enumerate()
-                .map(move |(x, item)| (Point::new(x, y), item))
-        })
+        let width = self.width;
+        self.cells
+            .iter()
+            .enumerate()
+            .map(move |(i, item)| (Point::new(i % width, i / width), item))
     }
 
     #[allow(dead_code)]
     pub fn iter_line(&self, start: Point, d: Direction) -> impl Iterator<Item = (Point, &T)> {
+        self.iter_line8(start, d.into())
+    }
+
+    pub fn iter_line8(&self, start: Point, d: Direction8) -> impl Iterator<Item = (Point, &T)> {
         LineIterator {
             g: self,
+            start,
             p: Some(start),
             d,
         }
     }
 
+    pub fn contains(&self, p: Point) -> bool {
+        p.x < self.width && p.y < self.height
+    }
+
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    pub fn with_topology(mut self, topology: Topology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    // Returns p + v if the result is inside the grid. Toroidal grids wrap
+    // the result instead.
+    pub fn offset(&self, p: Point, v: Vector) -> Option<Point> {
+        match self.topology {
+            Topology::Bounded => (p + v).filter(|&q| self.contains(q)),
+            Topology::Toroidal => {
+                let x = (p.x as i64 + v.x).rem_euclid(self.width as i64);
+                let y = (p.y as i64 + v.y).rem_euclid(self.height as i64);
+                Some(Point::new(x as usize, y as usize))
+            }
+        }
+    }
+
+    // The neighbour of p in direction d, following the grid's topology.
+    pub fn step(&self, p: Point, d: Direction) -> Option<Point> {
+        match self.topology {
+            Topology::Bounded => p.next(d).filter(|&q| self.contains(q)),
+            Topology::Toroidal => self.offset(p, d.into()),
+        }
+    }
+
+    // On toroidal grids narrower than three cells, a neighbour may be
+    // returned more than once or be p itself.
+    pub fn neighbors(&self, p: Point) -> impl Iterator<Item = Point> {
+        Direction::iter().filter_map(move |d| self.step(p, d))
+    }
+
+    pub fn neighbors8(&self, p: Point) -> impl Iterator<Item = Point> {
+        Direction8::iter().filter_map(move |d| self.offset(p, d.vector()))
+    }
+
+    // See Rect::quadrants.
+    pub fn quadrants(&self) -> [Rect; 4] {
+        self.bounds().quadrants()
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
     pub fn size(&self) -> (usize, usize) {
-        (self.cells[0].len(), self.cells.len())
+        (self.width, self.height)
+    }
+
+    pub fn bounds(&self) -> Rect {
+        Rect::new(0, 0, self.width, self.height)
+    }
+
+    // Starts rendering the grid as text. See Render.
+    pub fn render(&self) -> Render<'_, T> {
+        Render::new(self)
+    }
+
+    // Builds a grid by calling f for every point in row-major order. Panics
+    // if width or height is zero.
+    pub fn from_fn(width: usize, height: usize, mut f: impl FnMut(Point) -> T) -> Self {
+        assert!(
+            width > 0 && height > 0,
+            "invalid grid: {}",
+            GridError::Empty
+        );
+        let cells = (0..height)
+            .flat_map(|y| (0..width).map(move |x| Point::new(x, y)))
+            .map(&mut f)
+            .collect();
+        Grid {
+            cells,
+            width,
+            height,
+            topology: Topology::Bounded,
+        }
+    }
+
+    // A borrowed view of the part of the grid inside rect. The rect is
+    // clipped to the grid, so the view may be empty.
+    pub fn view(&self, rect: Rect) -> SubGrid<'_, T> {
+        SubGrid::new(self, self.bounds().intersect(rect))
+    }
+
+    // Every w x h view of the grid, in row-major order of their top left
+    // corner.
+    pub fn windows(&self, w: usize, h: usize) -> impl Iterator<Item = SubGrid<'_, T>> {
+        let xs = (self.width + 1).saturating_sub(w);
+        let ys = (self.height + 1).saturating_sub(h);
+        let ys = if w == 0 || h == 0 { 0 } else { ys };
+        (0..ys).flat_map(move |y| (0..xs).map(move |x| self.view(Rect::new(x, y, w, h))))
+    }
+}
+
+// The transformed grids keep the topology of the original.
+impl<T: Clone> Grid<T> {
+    // Rotates clockwise as drawn on screen: the top row becomes the right
+    // column.
+    pub fn rotate_cw(&self) -> Self {
+        let h = self.height;
+        Grid::from_fn(self.height, self.width, |p| {
+            self[Point::new(p.y, h - 1 - p.x)].clone()
+        })
+        .with_topology(self.topology)
+    }
+
+    // The top row becomes the left column.
+    pub fn rotate_ccw(&self) -> Self {
+        let w = self.width;
+        Grid::from_fn(self.height, self.width, |p| {
+            self[Point::new(w - 1 - p.y, p.x)].clone()
+        })
+        .with_topology(self.topology)
+    }
+
+    // Mirrors along the main diagonal: rows become columns.
+    pub fn transpose(&self) -> Self {
+        Grid::from_fn(self.height, self.width, |p| {
+            self[Point::new(p.y, p.x)].clone()
+        })
+        .with_topology(self.topology)
+    }
+
+    // Mirrors left to right.
+    pub fn flip_horizontal(&self) -> Self {
+        let w = self.width;
+        Grid::from_fn(self.width, self.height, |p| {
+            self[Point::new(w - 1 - p.x, p.y)].clone()
+        })
+        .with_topology(self.topology)
+    }
+
+    // Mirrors top to bottom.
+    pub fn flip_vertical(&self) -> Self {
+        let h = self.height;
+        Grid::from_fn(self.width, self.height, |p| {
+            self[Point::new(p.x, h - 1 - p.y)].clone()
+        })
+        .with_topology(self.topology)
+    }
+
+    // Copies the part of the grid inside rect. None if they do not overlap.
+    pub fn crop(&self, rect: Rect) -> Option<Self> {
+        self.view(rect).to_grid()
     }
 }
 
@@ -51,10 +353,159 @@ impl<T> From<Vec<Vec<T>>> for Grid<T> {
     }
 }
 
+impl<T> Index<Point> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, p: Point) -> &T {
+        self.get(p).expect("point out of bounds")
+    }
+}
+
+impl<T> IndexMut<Point> for Grid<T> {
+    fn index_mut(&mut self, p: Point) -> &mut T {
+        self.get_mut(p).expect("point out of bounds")
+    }
+}
+
+// A cell type that is written as a single character in puzzle input.
+pub trait FromGridChar: Sized {
+    fn from_char(c: char) -> Option<Self>;
+    fn to_char(&self) -> char;
+
+    // Marker cells, such as a start position, have their positions recorded
+    // when the grid is parsed.
+    fn is_marker(&self) -> bool {
+        false
+    }
+}
+
+// Decimal digits.
+impl FromGridChar for u8 {
+    fn from_char(c: char) -> Option<Self> {
+        c.to_digit(10).map(|d| d as u8)
+    }
+
+    fn to_char(&self) -> char {
+        char::from_digit(*self as u32, 10).unwrap_or('?')
+    }
+}
+
+// Filled and empty cells.
+impl FromGridChar for bool {
+    fn from_char(c: char) -> Option<Self> {
+        match c {
+            '#' => Some(true),
+            '.' => Some(false),
+            _ => None,
+        }
+    }
+
+    fn to_char(&self) -> char {
+        if *self { '#' } else { '.' }
+    }
+}
+
+impl<T: FromGridChar> fmt::Display for Grid<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for row in self.rows() {
+            let line: String = row.iter().map(|c| c.to_char()).collect();
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+// Positions of the marker cells of a parsed grid in row-major order.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Markers<T>(pub Vec<(Point, T)>);
+
+impl<T: PartialEq> Markers<T> {
+    // Returns the position of the first marker equal to cell.
+    pub fn find(&self, cell: &T) -> Option<Point> {
+        self.0.iter().find(|(_, c)| c == cell).map(|&(p, _)| p)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(Point, T)> {
+        self.0.iter()
+    }
+}
+
+// The points with x in x..x + width and y in y..y + height.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    pub fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    // The square of points within chebyshev distance r of center, clipped at
+    // zero.
+    pub fn around(center: Point, r: usize) -> Self {
+        let x = center.x.saturating_sub(r);
+        let y = center.y.saturating_sub(r);
+        Rect::new(x, y, center.x + r + 1 - x, center.y + r + 1 - y)
+    }
+
+    pub fn contains(&self, p: Point) -> bool {
+        (self.x..self.x + self.width).contains(&p.x)
+            && (self.y..self.y + self.height).contains(&p.y)
+    }
+
+    // The overlap of the two rects. Empty rects have zero width or height.
+    pub fn intersect(&self, other: Rect) -> Rect {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let x_end = (self.x + self.width).min(other.x + other.width);
+        let y_end = (self.y + self.height).min(other.y + other.height);
+        Rect::new(x, y, x_end.saturating_sub(x), y_end.saturating_sub(y))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    // Splits the rect around its centre lines into the NW, NE, SW and SE
+    // quadrants. With an odd width or height, the middle column or row is
+    // in no quadrant.
+    pub fn quadrants(&self) -> [Rect; 4] {
+        let (w, h) = (self.width / 2, self.height / 2);
+        let right = self.x + self.width - w;
+        let bottom = self.y + self.height - h;
+        [
+            Rect::new(self.x, self.y, w, h),
+            Rect::new(right, self.y, w, h),
+            Rect::new(self.x, bottom, w, h),
+            Rect::new(right, bottom, w, h),
+        ]
+    }
+
+    // The index into quadrants() of the quadrant containing p.
+    pub fn quadrant_of(&self, p: Point) -> Option<usize> {
+        self.quadrants().iter().position(|q| q.contains(p))
+    }
+
+    // The top left corner.
+    pub fn origin(&self) -> Point {
+        Point::new(self.x, self.y)
+    }
+}
+
 struct LineIterator<'a, T> {
     g: &'a Grid<T>,
+    start: Point,
     p: Option<Point>,
-    d: Direction,
+    d: Direction8,
 }
 
 impl<'a, T> Iterator for LineIterator<'a, T> {
@@ -63,7 +514,14 @@ impl<'a, T> Iterator for LineIterator<'a, T> {
     fn next(&mut self) -> Option<Self::Item> {
         let cur = self.p?;
         let ret = self.g.get(cur)?;
-        self.p = cur.next(self.d);
+        self.p = match self.g.topology {
+            Topology::Bounded => cur.next8(self.d),
+            // Stop once the line wraps around to where it started.
+            Topology::Toroidal => self
+                .g
+                .offset(cur, self.d.vector())
+                .filter(|&p| p != self.start),
+        };
 
         Some((cur, ret))
     }
@@ -82,73 +540,637 @@ impl Point {
 
     pub fn next(&self, d: Direction) -> Option<Point> {
         let p = match d {
-            Direction::Up => Point::new(self.x, self.y.checked_add(1)?),
-            Direction::Down => Point::new(self.x, self.y.checked_sub(1)?),
-            Direction::Left => Point::new(self.x.checked_sub(1)?, self.y),
-            Direction::Right => Point::new(self.x.checked_add(1)?, self.y),
+            Direction::North => Point::new(self.x, self.y.checked_sub(1)?),
+            Direction::South => Point::new(self.x, self.y.checked_add(1)?),
+            Direction::West => Point::new(self.x.checked_sub(1)?, self.y),
+            Direction::East => Point::new(self.x.checked_add(1)?, self.y),
         };
 
         Some(p)
     }
 
-    pub fn iter_adjacent(&self) -> impl Iterator<Item = Point> {
+    pub fn iter_adjacent(&self) -> impl Iterator<Item = Point> + use<> {
         let p = *self;
         Direction::iter().filter_map(move |d| p.next(d))
     }
 
-    pub fn iter_adjacent8(&self) -> impl Iterator<Item = Point> {
+    pub fn next8(&self, d: Direction8) -> Option<Point> {
+        *self + d.vector()
+    }
+
+    pub fn iter_adjacent8(&self) -> impl Iterator<Item = Point> + use<> {
+        let p = *self;
+        Direction8::iter().filter_map(move |d| p.next8(d))
+    }
+
+    pub fn manhattan_distance(&self, other: Point) -> usize {
+        self.x.abs_diff(other.x) + self.y.abs_diff(other.y)
+    }
+
+    pub fn chebyshev_distance(&self, other: Point) -> usize {
+        self.x.abs_diff(other.x).max(self.y.abs_diff(other.y))
+    }
+
+    // Every point with a manhattan distance of at most r, including self.
+    // Points with negative coordinates are skipped.
+    pub fn iter_manhattan(&self, r: usize) -> impl Iterator<Item = Point> {
+        let p = *self;
+        Vector::iter_manhattan(r as i64).filter_map(move |v| p + v)
+    }
+
+    // Every point with a chebyshev distance of at most r, including self.
+    // Points with negative coordinates are skipped.
+    pub fn iter_chebyshev(&self, r: usize) -> impl Iterator<Item = Point> {
         let p = *self;
-        let simple_dir = self.iter_adjacent();
-        let compound_dir = Direction::iter()
-            .flat_map(|d1| std::iter::repeat(d1).zip(Direction::iter()))
-            .filter(|(a, b)| a != b && a.opposite() != *b)
-            .filter_map(move |(a, b)| p.next(a).and_then(|x| x.next(b)));
-        simple_dir.chain(compound_dir)
+        Vector::iter_chebyshev(r as i64).filter_map(move |v| p + v)
+    }
+}
+
+// Checked: None if the result has a negative coordinate. Use Grid::offset to
+// also check against the grid bounds.
+impl Add<Vector> for Point {
+    type Output = Option<Point>;
+
+    fn add(self, v: Vector) -> Option<Point> {
+        let x = (self.x as i64).checked_add(v.x)?;
+        let y = (self.y as i64).checked_add(v.y)?;
+        Some(Point::new(x.try_into().ok()?, y.try_into().ok()?))
+    }
+}
+
+impl Sub<Vector> for Point {
+    type Output = Option<Point>;
+
+    fn sub(self, v: Vector) -> Option<Point> {
+        self + -v
+    }
+}
+
+impl Sub for Point {
+    type Output = Vector;
+
+    fn sub(self, other: Point) -> Vector {
+        Vector::new(
+            self.x as i64 - other.x as i64,
+            self.y as i64 - other.y as i64,
+        )
+    }
+}
+
+// A signed offset between two points.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct Vector {
+    pub x: i64,
+    pub y: i64,
+}
+
+impl Vector {
+    pub const ZERO: Vector = Vector::new(0, 0);
+
+    pub const fn new(x: i64, y: i64) -> Self {
+        Vector { x, y }
+    }
+
+    pub fn manhattan_len(&self) -> u64 {
+        self.x.unsigned_abs() + self.y.unsigned_abs()
+    }
+
+    pub fn chebyshev_len(&self) -> u64 {
+        self.x.unsigned_abs().max(self.y.unsigned_abs())
+    }
+
+    // Every vector with a manhattan length of at most r.
+    pub fn iter_manhattan(r: i64) -> impl Iterator<Item = Vector> {
+        (-r..=r).flat_map(move |y| {
+            let rem = r - y.abs();
+            (-rem..=rem).map(move |x| Vector::new(x, y))
+        })
+    }
+
+    // Every vector with a chebyshev length of at most r.
+    pub fn iter_chebyshev(r: i64) -> impl Iterator<Item = Vector> {
+        (-r..=r).flat_map(move |y| (-r..=r).map(move |x| Vector::new(x, y)))
+    }
+}
+
+impl From<Direction> for Vector {
+    fn from(d: Direction) -> Self {
+        match d {
+            Direction::North => Vector::new(0, -1),
+            Direction::South => Vector::new(0, 1),
+            Direction::West => Vector::new(-1, 0),
+            Direction::East => Vector::new(1, 0),
+        }
+    }
+}
+
+impl Add for Vector {
+    type Output = Vector;
+
+    fn add(self, other: Vector) -> Vector {
+        Vector::new(self.x + other.x, self.y + other.y)
     }
 }
 
+impl AddAssign for Vector {
+    fn add_assign(&mut self, other: Vector) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for Vector {
+    type Output = Vector;
+
+    fn sub(self, other: Vector) -> Vector {
+        Vector::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl SubAssign for Vector {
+    fn sub_assign(&mut self, other: Vector) {
+        *self = *self - other;
+    }
+}
+
+impl Mul<i64> for Vector {
+    type Output = Vector;
+
+    fn mul(self, n: i64) -> Vector {
+        Vector::new(self.x * n, self.y * n)
+    }
+}
+
+impl Neg for Vector {
+    type Output = Vector;
+
+    fn neg(self) -> Vector {
+        Vector::new(-self.x, -self.y)
+    }
+}
+
+// Directions on screen, with row 0 of a parsed grid at the top: North
+// decreases y and rotate_cw turns clockwise as drawn.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Direction {
-    Up = 0,
-    Right = 1,
-    Down = 2,
-    Left = 3,
+    North = 0,
+    East = 1,
+    South = 2,
+    West = 3,
 }
 
 impl Direction {
     pub fn iter() -> impl Iterator<Item = Self> {
         [
-            Direction::Up,
-            Direction::Right,
-            Direction::Down,
-            Direction::Left,
+            Direction::North,
+            Direction::East,
+            Direction::South,
+            Direction::West,
         ]
         .into_iter()
     }
 
     pub fn opposite(&self) -> Self {
         match self {
-            Direction::Up => Direction::Down,
-            Direction::Down => Direction::Up,
-            Direction::Left => Direction::Right,
-            Direction::Right => Direction::Left,
+            Direction::North => Direction::South,
+            Direction::South => Direction::North,
+            Direction::West => Direction::East,
+            Direction::East => Direction::West,
         }
     }
 
-    pub fn rotate_left(&self) -> Self {
+    pub fn rotate_cw(&self) -> Self {
         match self {
-            Direction::Up => Direction::Left,
-            Direction::Down => Direction::Right,
-            Direction::Left => Direction::Down,
-            Direction::Right => Direction::Up,
+            Direction::North => Direction::East,
+            Direction::East => Direction::South,
+            Direction::South => Direction::West,
+            Direction::West => Direction::North,
         }
     }
-    pub fn rotate_right(&self) -> Self {
+
+    pub fn rotate_ccw(&self) -> Self {
         match self {
-            Direction::Up => Direction::Right,
-            Direction::Down => Direction::Left,
-            Direction::Left => Direction::Up,
-            Direction::Right => Direction::Down,
+            Direction::North => Direction::West,
+            Direction::West => Direction::South,
+            Direction::South => Direction::East,
+            Direction::East => Direction::North,
+        }
+    }
+}
+
+// The eight compass directions in clockwise order, using the same screen
+// convention as Direction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Direction8 {
+    N = 0,
+    NE = 1,
+    E = 2,
+    SE = 3,
+    S = 4,
+    SW = 5,
+    W = 6,
+    NW = 7,
+}
+
+impl Direction8 {
+    const ALL: [Direction8; 8] = [
+        Direction8::N,
+        Direction8::NE,
+        Direction8::E,
+        Direction8::SE,
+        Direction8::S,
+        Direction8::SW,
+        Direction8::W,
+        Direction8::NW,
+    ];
+
+    pub fn iter() -> impl Iterator<Item = Self> {
+        Self::ALL.into_iter()
+    }
+
+    // Rotates clockwise by steps of 45 degrees.
+    fn rotate(&self, steps: usize) -> Self {
+        Self::ALL[(*self as usize + steps) % 8]
+    }
+
+    pub fn rotate_cw45(&self) -> Self {
+        self.rotate(1)
+    }
+
+    pub fn rotate_ccw45(&self) -> Self {
+        self.rotate(7)
+    }
+
+    pub fn rotate_cw(&self) -> Self {
+        self.rotate(2)
+    }
+
+    pub fn rotate_ccw(&self) -> Self {
+        self.rotate(6)
+    }
+
+    pub fn opposite(&self) -> Self {
+        self.rotate(4)
+    }
+
+    pub fn is_diagonal(&self) -> bool {
+        *self as usize % 2 == 1
+    }
+
+    pub fn vector(&self) -> Vector {
+        match self {
+            Direction8::N => Vector::new(0, -1),
+            Direction8::NE => Vector::new(1, -1),
+            Direction8::E => Vector::new(1, 0),
+            Direction8::SE => Vector::new(1, 1),
+            Direction8::S => Vector::new(0, 1),
+            Direction8::SW => Vector::new(-1, 1),
+            Direction8::W => Vector::new(-1, 0),
+            Direction8::NW => Vector::new(-1, -1),
+        }
+    }
+}
+
+impl From<Direction> for Direction8 {
+    fn from(d: Direction) -> Self {
+        match d {
+            Direction::North => Direction8::N,
+            Direction::East => Direction8::E,
+            Direction::South => Direction8::S,
+            Direction::West => Direction8::W,
+        }
+    }
+}
+
+// Fails for diagonal directions.
+impl TryFrom<Direction8> for Direction {
+    type Error = Direction8;
+
+    fn try_from(d: Direction8) -> Result<Self, Self::Error> {
+        match d {
+            Direction8::N => Ok(Direction::North),
+            Direction8::E => Ok(Direction::East),
+            Direction8::S => Ok(Direction::South),
+            Direction8::W => Ok(Direction::West),
+            _ => Err(d),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn test_grid() -> Grid<u8> {
+        Grid::new(vec![vec![1, 2, 3], vec![4, 5, 6]])
+    }
+
+    #[test]
+    fn get_test() {
+        let mut g = test_grid();
+        assert_eq!(g.size(), (3, 2));
+        assert_eq!(g.get(Point::new(2, 1)), Some(&6));
+        assert_eq!(g.get(Point::new(3, 0)), None);
+        assert_eq!(g.get(Point::new(0, 2)), None);
+
+        g[Point::new(1, 0)] = 7;
+        assert_eq!(g[Point::new(1, 0)], 7);
+        assert_eq!(g.index_of(Point::new(1, 1)), Some(4));
+        assert_eq!(g.point_at(4), Point::new(1, 1));
+    }
+
+    #[test]
+    fn row_column_test() {
+        let g = test_grid();
+        assert_eq!(g.row(1), Some(&[4, 5, 6][..]));
+        assert_eq!(g.row(2), None);
+        assert_eq!(g.column(1).copied().collect::<Vec<_>>(), vec![2, 5]);
+        assert_eq!(g.column(3).count(), 0);
+        assert_eq!(g.rows().count(), 2);
+    }
+
+    #[test]
+    fn iter_test() {
+        let g = test_grid();
+        let items: Vec<_> = g.iter_items().map(|(p, &v)| (p.x, p.y, v)).collect();
+        assert_eq!(items[4], (1, 1, 5));
+        assert!(g.iter_points().eq(g.iter_items().map(|(p, _)| p)));
+
+        let line: Vec<_> = g
+            .iter_line(Point::new(0, 0), Direction::East)
+            .map(|(_, &v)| v)
+            .collect();
+        assert_eq!(line, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn vector_ops_test() {
+        let a = Vector::new(2, -3);
+        let b = Vector::new(-1, 5);
+        assert_eq!(a + b, Vector::new(1, 2));
+        assert_eq!(a - b, Vector::new(3, -8));
+        assert_eq!(a * 3, Vector::new(6, -9));
+        assert_eq!(-a, Vector::new(-2, 3));
+        assert_eq!(a.manhattan_len(), 5);
+        assert_eq!(a.chebyshev_len(), 3);
+        assert_eq!(Vector::from(Direction::West), Vector::new(-1, 0));
+    }
+
+    #[test]
+    fn point_vector_test() {
+        let p = Point::new(3, 4);
+        assert_eq!(p + Vector::new(-3, 2), Some(Point::new(0, 6)));
+        assert_eq!(p + Vector::new(-4, 0), None);
+        assert_eq!(p - Vector::new(1, 1), Some(Point::new(2, 3)));
+        assert_eq!(Point::new(1, 9) - p, Vector::new(-2, 5));
+        assert_eq!(p.manhattan_distance(Point::new(1, 9)), 7);
+        assert_eq!(p.chebyshev_distance(Point::new(1, 9)), 5);
+
+        let g = test_grid();
+        assert_eq!(
+            g.offset(Point::new(0, 0), Vector::new(2, 1)),
+            Some(Point::new(2, 1))
+        );
+        assert_eq!(g.offset(Point::new(0, 0), Vector::new(3, 0)), None);
+    }
+
+    #[test]
+    fn iter_radius_test() {
+        for r in 0..5 {
+            let n = Vector::iter_manhattan(r).count() as i64;
+            assert_eq!(n, 2 * r * (r + 1) + 1);
+            assert!(Vector::iter_manhattan(r).all(|v| v.manhattan_len() <= r as u64));
+
+            let n = Vector::iter_chebyshev(r).count() as i64;
+            assert_eq!(n, (2 * r + 1) * (2 * r + 1));
+        }
+
+        let p = Point::new(5, 5);
+        assert!(p.iter_manhattan(2).all(|q| p.manhattan_distance(q) <= 2));
+        assert_eq!(p.iter_manhattan(2).count(), 13);
+        assert_eq!(p.iter_chebyshev(1).count(), 9);
+
+        // Near the origin, points with negative coordinates are skipped.
+        assert_eq!(Point::new(0, 0).iter_manhattan(1).count(), 3);
+        assert_eq!(Point::new(0, 0).iter_chebyshev(1).count(), 4);
+    }
+
+    #[test]
+    fn direction_test() {
+        use Direction::*;
+
+        let p = Point::new(1, 1);
+        assert_eq!(p.next(North), Some(Point::new(1, 0)));
+        assert_eq!(p.next(South), Some(Point::new(1, 2)));
+        assert_eq!(p.next(West), Some(Point::new(0, 1)));
+        assert_eq!(p.next(East), Some(Point::new(2, 1)));
+
+        for (d, cw, ccw) in [
+            (North, East, West),
+            (East, South, North),
+            (South, West, East),
+            (West, North, South),
+        ] {
+            assert_eq!(d.rotate_cw(), cw);
+            assert_eq!(d.rotate_ccw(), ccw);
+            assert_eq!(d.rotate_cw().rotate_cw(), d.opposite());
+            assert_eq!(p + Vector::from(d), p.next(d));
+        }
+
+        // North is the row above on screen.
+        let g = test_grid();
+        assert_eq!(g.get(Point::new(0, 1).next(North).unwrap()), Some(&1));
+    }
+
+    #[test]
+    fn direction8_test() {
+        assert_eq!(Direction8::N.rotate_cw45(), Direction8::NE);
+        assert_eq!(Direction8::N.rotate_ccw45(), Direction8::NW);
+        assert_eq!(Direction8::NW.rotate_cw(), Direction8::NE);
+        assert_eq!(Direction8::E.rotate_ccw(), Direction8::N);
+        assert_eq!(Direction8::NE.vector(), Vector::new(1, -1));
+        assert_eq!(Direction8::SW.opposite(), Direction8::NE);
+
+        for d in Direction8::iter() {
+            assert_eq!(d.vector(), -d.opposite().vector());
+            assert_eq!(d.rotate_cw45().rotate_ccw45(), d);
+            assert_eq!(d.is_diagonal(), Direction::try_from(d).is_err());
+        }
+
+        for d in Direction::iter() {
+            let d8 = Direction8::from(d);
+            assert_eq!(Direction::try_from(d8), Ok(d));
+            assert_eq!(d8.vector(), Vector::from(d));
+            assert_eq!(Direction8::from(d.rotate_cw()), d8.rotate_cw());
+            assert_eq!(Direction8::from(d.rotate_ccw()), d8.rotate_ccw());
+        }
+    }
+
+    #[test]
+    fn adjacent8_test() {
+        assert_eq!(Point::new(1, 1).iter_adjacent8().count(), 8);
+        let mut corner: Vec<_> = Point::new(0, 0).iter_adjacent8().collect();
+        corner.sort();
+        assert_eq!(
+            corner,
+            vec![Point::new(0, 1), Point::new(1, 0), Point::new(1, 1)]
+        );
+    }
+
+    #[test]
+    fn iter_line8_test() {
+        let g = Grid::new(vec![vec![1, 2, 3], vec![4, 5, 6], vec![7, 8, 9]]);
+        let diag: Vec<_> = g
+            .iter_line8(Point::new(0, 2), Direction8::NE)
+            .map(|(_, &v)| v)
+            .collect();
+        assert_eq!(diag, vec![7, 5, 3]);
+
+        let anti: Vec<_> = g
+            .iter_line8(Point::new(2, 2), Direction8::NW)
+            .map(|(_, &v)| v)
+            .collect();
+        assert_eq!(anti, vec![9, 5, 1]);
+    }
+
+    #[test]
+    fn try_new_test() {
+        assert_eq!(Grid::<u8>::try_new(vec![]), Err(GridError::Empty));
+        assert_eq!(Grid::<u8>::try_new(vec![vec![]]), Err(GridError::Empty));
+        assert_eq!(
+            Grid::try_new(vec![vec![1, 2], vec![3, 4], vec![5]]),
+            Err(GridError::Ragged {
+                row: 2,
+                expected: 2,
+                actual: 1
+            })
+        );
+        assert_eq!(
+            Grid::try_new(vec![vec![1], vec![2]]).map(|g| g.size()),
+            Ok((1, 2))
+        );
+    }
+
+    #[test]
+    fn transform_test() {
+        // 1 2 3
+        // 4 5 6
+        let g = test_grid();
+        assert_eq!(
+            g.rotate_cw(),
+            Grid::new(vec![vec![4, 1], vec![5, 2], vec![6, 3]])
+        );
+        assert_eq!(
+            g.rotate_ccw(),
+            Grid::new(vec![vec![3, 6], vec![2, 5], vec![1, 4]])
+        );
+        assert_eq!(
+            g.transpose(),
+            Grid::new(vec![vec![1, 4], vec![2, 5], vec![3, 6]])
+        );
+        assert_eq!(
+            g.flip_horizontal(),
+            Grid::new(vec![vec![3, 2, 1], vec![6, 5, 4]])
+        );
+        assert_eq!(
+            g.flip_vertical(),
+            Grid::new(vec![vec![4, 5, 6], vec![1, 2, 3]])
+        );
+    }
+
+    #[test]
+    fn toroidal_test() {
+        let g = test_grid().with_topology(Topology::Toroidal);
+        let corner = Point::new(0, 0);
+        assert_eq!(
+            g.offset(corner, Vector::new(-1, -1)),
+            Some(Point::new(2, 1))
+        );
+        assert_eq!(g.offset(corner, Vector::new(7, 5)), Some(Point::new(1, 1)));
+        assert_eq!(g.step(corner, Direction::West), Some(Point::new(2, 0)));
+        assert_eq!(g.neighbors(corner).count(), 4);
+        assert_eq!(g.neighbors8(Point::new(1, 0)).count(), 8);
+
+        let line: Vec<_> = g
+            .iter_line(Point::new(1, 0), Direction::East)
+            .map(|(_, &v)| v)
+            .collect();
+        assert_eq!(line, vec![2, 3, 1]);
+        let diag: Vec<_> = g
+            .iter_line8(Point::new(0, 0), Direction8::SE)
+            .map(|(_, &v)| v)
+            .collect();
+        assert_eq!(diag, vec![1, 5, 3, 4, 2, 6]);
+
+        let bounded = test_grid();
+        assert_eq!(bounded.step(corner, Direction::West), None);
+        assert_eq!(bounded.neighbors(corner).count(), 2);
+        assert_eq!(bounded.neighbors8(corner).count(), 3);
+    }
+
+    #[test]
+    fn quadrants_test() {
+        // The layout of AoC 2024 day 14's example.
+        let r = Rect::new(0, 0, 11, 7);
+        assert_eq!(
+            r.quadrants(),
+            [
+                Rect::new(0, 0, 5, 3),
+                Rect::new(6, 0, 5, 3),
+                Rect::new(0, 4, 5, 3),
+                Rect::new(6, 4, 5, 3),
+            ]
+        );
+        assert_eq!(r.quadrant_of(Point::new(5, 0)), None);
+        assert_eq!(r.quadrant_of(Point::new(0, 3)), None);
+        assert_eq!(r.quadrant_of(Point::new(10, 6)), Some(3));
+
+        let g = Grid::from_fn(4, 4, |_| 0u8);
+        let total: usize = g.quadrants().iter().map(|q| q.width * q.height).sum();
+        assert_eq!(total, 16);
+        assert_eq!(g.bounds().quadrant_of(Point::new(2, 1)), Some(1));
+    }
+
+    fn arb_grid() -> impl Strategy<Value = Grid<u8>> {
+        (1..8usize, 1..8usize).prop_flat_map(|(w, h)| {
+            prop::collection::vec(any::<u8>(), w * h).prop_map(move |cells| Grid {
+                cells,
+                width: w,
+                height: h,
+                topology: Topology::Bounded,
+            })
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn four_rotations_prop(g in arb_grid()) {
+            let cw = g.rotate_cw().rotate_cw().rotate_cw().rotate_cw();
+            prop_assert_eq!(&cw, &g);
+            prop_assert_eq!(g.rotate_cw().rotate_ccw(), g.clone());
+            prop_assert_eq!(g.rotate_cw().rotate_cw(), g.flip_horizontal().flip_vertical());
+        }
+
+        #[test]
+        fn transpose_prop(g in arb_grid()) {
+            prop_assert_eq!(g.transpose(), g.rotate_cw().flip_horizontal());
+            prop_assert_eq!(g.transpose(), g.rotate_ccw().flip_vertical());
+            prop_assert_eq!(g.transpose().transpose(), g.clone());
+        }
+
+        #[test]
+        fn crop_prop(g in arb_grid(), x in 0..8usize, y in 0..8usize, w in 1..8usize, h in 1..8usize) {
+            let rect = Rect::new(x, y, w, h);
+            match g.crop(rect) {
+                Some(c) => {
+                    for (p, v) in c.iter_items() {
+                        prop_assert_eq!(g.get(Point::new(p.x + x, p.y + y)), Some(v));
+                    }
+                    prop_assert_eq!(c.size(), ((x + w).min(g.width()) - x, (y + h).min(g.height()) - y));
+                }
+                None => prop_assert!(g.bounds().intersect(rect).is_empty()),
+            }
         }
     }
 }
@@ -0,0 +1,309 @@
+// Dense boolean grids for visited sets and masks.
+//
+// BitGrid stores one bit per cell. DirectionBitGrid stores one bit per
+// (cell, Direction) pair, for searches whose state is a position and a
+// heading. Both have the same insert/contains API as a HashSet so they can
+// replace one directly.
+
+use super::{Direction, Grid, Point};
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BitGrid {
+    words: Vec<u64>,
+    width: usize,
+    height: usize,
+}
+
+impl BitGrid {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            words: vec![0; (width * height).div_ceil(64)],
+            width,
+            height,
+        }
+    }
+
+    // An empty BitGrid the same size as grid.
+    pub fn for_grid<T>(grid: &Grid<T>) -> Self {
+        Self::new(grid.width(), grid.height())
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    fn index(&self, p: Point) -> Option<usize> {
+        (p.x < self.width && p.y < self.height).then_some(p.y * self.width + p.x)
+    }
+
+    // False for points outside the grid.
+    pub fn contains(&self, p: Point) -> bool {
+        self.index(p)
+            .is_some_and(|i| self.words[i / 64] & (1 << (i % 64)) != 0)
+    }
+
+    // Sets p and returns true if it was not set before. Panics if p is
+    // outside the grid.
+    pub fn insert(&mut self, p: Point) -> bool {
+        let i = self.index(p).expect("point outside of bit grid");
+        let word = &mut self.words[i / 64];
+        let mask = 1 << (i % 64);
+        let ret = *word & mask == 0;
+        *word |= mask;
+        ret
+    }
+
+    // Clears p and returns true if it was set.
+    pub fn remove(&mut self, p: Point) -> bool {
+        let Some(i) = self.index(p) else {
+            return false;
+        };
+        let word = &mut self.words[i / 64];
+        let mask = 1 << (i % 64);
+        let ret = *word & mask != 0;
+        *word &= !mask;
+        ret
+    }
+
+    pub fn clear(&mut self) {
+        self.words.fill(0);
+    }
+
+    // The number of set points.
+    pub fn count(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|&w| w == 0)
+    }
+
+    // Sets every point that is set in other. Panics if the sizes differ.
+    pub fn union_with(&mut self, other: &BitGrid) {
+        assert_eq!(self.size(), other.size(), "bit grid sizes differ");
+        for (a, b) in self.words.iter_mut().zip(&other.words) {
+            *a |= b;
+        }
+    }
+
+    // Clears every point that is not set in other. Panics if the sizes
+    // differ.
+    pub fn intersect_with(&mut self, other: &BitGrid) {
+        assert_eq!(self.size(), other.size(), "bit grid sizes differ");
+        for (a, b) in self.words.iter_mut().zip(&other.words) {
+            *a &= b;
+        }
+    }
+
+    // The set points in row-major order.
+    pub fn iter(&self) -> impl Iterator<Item = Point> + '_ {
+        self.words.iter().enumerate().flat_map(move |(wi, &w)| {
+            let mut w = w;
+            std::iter::from_fn(move || {
+                if w == 0 {
+                    return None;
+                }
+                let i = wi * 64 + w.trailing_zeros() as usize;
+                w &= w - 1;
+                Some(Point::new(i % self.width, i / self.width))
+            })
+        })
+    }
+}
+
+impl FromIterator<Point> for BitGrid {
+    // The grid is sized to fit the largest x and y.
+    fn from_iter<I: IntoIterator<Item = Point>>(iter: I) -> Self {
+        let points: Vec<_> = iter.into_iter().collect();
+        let width = points.iter().map(|p| p.x + 1).max().unwrap_or(0);
+        let height = points.iter().map(|p| p.y + 1).max().unwrap_or(0);
+        let mut ret = BitGrid::new(width, height);
+        for p in points {
+            ret.insert(p);
+        }
+        ret
+    }
+}
+
+// One bit per (cell, direction) pair, packed as a 4 bit mask per cell.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DirectionBitGrid {
+    cells: Vec<u8>,
+    width: usize,
+    height: usize,
+}
+
+impl DirectionBitGrid {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            cells: vec![0; width * height],
+            width,
+            height,
+        }
+    }
+
+    pub fn for_grid<T>(grid: &Grid<T>) -> Self {
+        Self::new(grid.width(), grid.height())
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    fn index(&self, p: Point) -> Option<usize> {
+        (p.x < self.width && p.y < self.height).then_some(p.y * self.width + p.x)
+    }
+
+    // The directions set at p as a bitmask indexed by Direction as u8.
+    pub fn mask(&self, p: Point) -> u8 {
+        self.index(p).map_or(0, |i| self.cells[i])
+    }
+
+    pub fn contains(&self, p: Point, d: Direction) -> bool {
+        self.mask(p) & (1 << d as u8) != 0
+    }
+
+    // Sets (p, d) and returns true if it was not set before. Panics if p is
+    // outside the grid.
+    pub fn insert(&mut self, p: Point, d: Direction) -> bool {
+        let i = self.index(p).expect("point outside of bit grid");
+        let mask = 1 << d as u8;
+        let ret = self.cells[i] & mask == 0;
+        self.cells[i] |= mask;
+        ret
+    }
+
+    pub fn remove(&mut self, p: Point, d: Direction) -> bool {
+        let Some(i) = self.index(p) else {
+            return false;
+        };
+        let mask = 1 << d as u8;
+        let ret = self.cells[i] & mask != 0;
+        self.cells[i] &= !mask;
+        ret
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.fill(0);
+    }
+
+    // The number of set (point, direction) pairs.
+    pub fn count(&self) -> usize {
+        self.cells.iter().map(|m| m.count_ones() as usize).sum()
+    }
+
+    // The points with at least one direction set.
+    pub fn points(&self) -> BitGrid {
+        let mut ret = BitGrid::new(self.width, self.height);
+        for (i, _) in self.cells.iter().enumerate().filter(|(_, m)| **m != 0) {
+            ret.insert(Point::new(i % self.width, i / self.width));
+        }
+        ret
+    }
+
+    // Every set (point, direction) pair in row-major order.
+    pub fn iter(&self) -> impl Iterator<Item = (Point, Direction)> + '_ {
+        self.cells
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| **m != 0)
+            .flat_map(move |(i, &m)| {
+                let p = Point::new(i % self.width, i / self.width);
+                Direction::iter()
+                    .filter(move |&d| m & (1 << d as u8) != 0)
+                    .map(move |d| (p, d))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ahash::AHashSet;
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    use super::*;
+
+    #[test]
+    fn bit_grid_test() {
+        // 130 cells span three words.
+        let mut g = BitGrid::new(13, 10);
+        assert!(g.insert(Point::new(0, 0)));
+        assert!(!g.insert(Point::new(0, 0)));
+        assert!(g.insert(Point::new(12, 4)));
+        assert!(g.insert(Point::new(12, 9)));
+        assert!(g.contains(Point::new(12, 4)));
+        assert!(!g.contains(Point::new(11, 4)));
+        assert!(!g.contains(Point::new(13, 0)));
+        assert_eq!(g.count(), 3);
+        assert_eq!(
+            g.iter().collect::<Vec<_>>(),
+            vec![Point::new(0, 0), Point::new(12, 4), Point::new(12, 9)]
+        );
+
+        assert!(g.remove(Point::new(12, 4)));
+        assert!(!g.remove(Point::new(12, 4)));
+        assert_eq!(g.count(), 2);
+
+        let mut other = BitGrid::new(13, 10);
+        other.insert(Point::new(0, 0));
+        other.insert(Point::new(5, 5));
+        let mut union = g.clone();
+        union.union_with(&other);
+        assert_eq!(union.count(), 3);
+        g.intersect_with(&other);
+        assert_eq!(g.iter().collect::<Vec<_>>(), vec![Point::new(0, 0)]);
+
+        g.clear();
+        assert!(g.is_empty());
+    }
+
+    #[test]
+    fn bit_grid_matches_hash_set_test() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut bits = BitGrid::new(37, 23);
+        let mut set = AHashSet::new();
+        for _ in 0..2000 {
+            let p = Point::new(rng.random_range(0..37), rng.random_range(0..23));
+            if rng.random_bool(0.3) {
+                assert_eq!(bits.remove(p), set.remove(&p));
+            } else {
+                assert_eq!(bits.insert(p), set.insert(p));
+            }
+        }
+
+        assert_eq!(bits.count(), set.len());
+        let mut expected: Vec<_> = set.into_iter().collect();
+        expected.sort_by_key(|p| (p.y, p.x));
+        assert_eq!(bits.iter().collect::<Vec<_>>(), expected);
+        assert_eq!(
+            BitGrid::from_iter(expected.iter().copied()).count(),
+            expected.len()
+        );
+    }
+
+    #[test]
+    fn direction_bit_grid_test() {
+        let mut g = DirectionBitGrid::new(3, 3);
+        assert!(g.insert(Point::new(1, 1), Direction::North));
+        assert!(g.insert(Point::new(1, 1), Direction::West));
+        assert!(!g.insert(Point::new(1, 1), Direction::North));
+        assert!(g.insert(Point::new(2, 0), Direction::South));
+
+        assert!(g.contains(Point::new(1, 1), Direction::West));
+        assert!(!g.contains(Point::new(1, 1), Direction::East));
+        assert!(!g.contains(Point::new(5, 5), Direction::East));
+        assert_eq!(g.count(), 3);
+        assert_eq!(g.points().count(), 2);
+        assert_eq!(
+            g.iter().collect::<Vec<_>>(),
+            vec![
+                (Point::new(2, 0), Direction::South),
+                (Point::new(1, 1), Direction::North),
+                (Point::new(1, 1), Direction::West),
+            ]
+        );
+
+        assert!(g.remove(Point::new(1, 1), Direction::North));
+        assert_eq!(g.mask(Point::new(1, 1)), 1 << Direction::West as u8);
+    }
+}
@@ -0,0 +1,246 @@
+// Connected components of a grid: maximal groups of cells that are linked
+// through neighbours of the same region.
+//
+// Perimeter and sides always describe the region's outline on the square
+// grid, so two cells that only touch diagonally each contribute their own
+// edges even when 8-connectivity puts them in the same region.
+//
+// Neighbours follow the grid's topology, so on a toroidal grid a region can
+// wrap around an edge and only the cells of other regions bound it.
+
+use super::distance::Neighborhood;
+use super::{Direction, Grid, Point, Rect};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Region {
+    // Any cell of the region, the first in row-major order.
+    pub start: Point,
+    pub area: usize,
+    // The number of unit edges between the region and other cells or the
+    // outside of the grid.
+    pub perimeter: usize,
+    // The number of straight sides of the outline, which equals the number
+    // of corners.
+    pub sides: usize,
+    pub bounds: Rect,
+    pub touches_border: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Components {
+    // The index into regions of each cell's region.
+    pub labels: Grid<usize>,
+    pub regions: Vec<Region>,
+}
+
+impl Components {
+    pub fn label(&self, p: Point) -> Option<usize> {
+        self.labels.get(p).copied()
+    }
+
+    pub fn region_of(&self, p: Point) -> Option<&Region> {
+        self.label(p).map(|l| &self.regions[l])
+    }
+
+    // The cells of region label in row-major order.
+    pub fn cells(&self, label: usize) -> impl Iterator<Item = Point> + '_ {
+        self.labels
+            .iter_items()
+            .filter(move |&(_, &l)| l == label)
+            .map(|(p, _)| p)
+    }
+}
+
+// Labels the regions of grid. Adjacent cells a and b are in the same region
+// if same_region(a, b). Regions are numbered in row-major order of their
+// first cell.
+pub fn components<T>(
+    grid: &Grid<T>,
+    same_region: impl Fn(&T, &T) -> bool,
+    connectivity: Neighborhood,
+) -> Components {
+    let mut labels: Grid<Option<usize>> =
+        Grid::from_fn(grid.width(), grid.height(), |_| None).with_topology(grid.topology());
+    let mut regions = Vec::new();
+    let mut stack = Vec::new();
+
+    for start in grid.iter_points() {
+        if labels[start].is_some() {
+            continue;
+        }
+
+        let label = regions.len();
+        labels[start] = Some(label);
+        stack.push(start);
+        let mut cells = Vec::new();
+        while let Some(cur) = stack.pop() {
+            cells.push(cur);
+            for next in connectivity.neighbors(grid, cur) {
+                if labels[next].is_none() && same_region(&grid[cur], &grid[next]) {
+                    labels[next] = Some(label);
+                    stack.push(next);
+                }
+            }
+        }
+
+        regions.push(region_stats(grid, &labels, label, start, &cells));
+    }
+
+    Components {
+        labels: Grid::from_fn(grid.width(), grid.height(), |p| labels[p].unwrap())
+            .with_topology(grid.topology()),
+        regions,
+    }
+}
+
+fn region_stats<T>(
+    grid: &Grid<T>,
+    labels: &Grid<Option<usize>>,
+    label: usize,
+    start: Point,
+    cells: &[Point],
+) -> Region {
+    let inside = |p: Option<Point>| p.and_then(|p| labels.get(p).copied().flatten()) == Some(label);
+
+    let mut perimeter = 0;
+    let mut sides = 0;
+    for &p in cells {
+        for d in Direction::iter() {
+            let a = inside(labels.step(p, d));
+            let b = inside(labels.step(p, d.rotate_cw()));
+            perimeter += usize::from(!a);
+
+            // Convex corners have both sides outside. Concave corners have
+            // both sides inside but the diagonal between them outside.
+            let diagonal = labels
+                .step(p, d)
+                .and_then(|q| labels.step(q, d.rotate_cw()));
+            if (!a && !b) || (a && b && !inside(diagonal)) {
+                sides += 1;
+            }
+        }
+    }
+
+    let (min_x, max_x) = (
+        cells.iter().map(|p| p.x).min().unwrap(),
+        cells.iter().map(|p| p.x).max().unwrap(),
+    );
+    let (min_y, max_y) = (
+        cells.iter().map(|p| p.y).min().unwrap(),
+        cells.iter().map(|p| p.y).max().unwrap(),
+    );
+    let (width, height) = grid.size();
+
+    Region {
+        start,
+        area: cells.len(),
+        perimeter,
+        sides,
+        bounds: Rect::new(min_x, min_y, max_x - min_x + 1, max_y - min_y + 1),
+        touches_border: min_x == 0 || min_y == 0 || max_x == width - 1 || max_y == height - 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::Topology;
+
+    fn char_grid(s: &str) -> Grid<char> {
+        Grid::new(s.lines().map(|l| l.trim().chars().collect()).collect())
+    }
+
+    // AoC 2024 day 12: the fence prices by perimeter and by sides.
+    fn prices(s: &str) -> (usize, usize) {
+        let c = components(&char_grid(s), |a, b| a == b, Neighborhood::Four);
+        c.regions.iter().fold((0, 0), |(p, s), r| {
+            (p + r.area * r.perimeter, s + r.area * r.sides)
+        })
+    }
+
+    #[test]
+    fn fence_price_test() {
+        assert_eq!(prices("AAAA\nBBCD\nBBCC\nEEEC"), (140, 80));
+        assert_eq!(prices("OOOOO\nOXOXO\nOOOOO\nOXOXO\nOOOOO"), (772, 436));
+        assert_eq!(
+            prices(
+                "RRRRIICCFF
+                RRRRIICCCF
+                VVRRRCCFFF
+                VVRCCCJFFF
+                VVVVCJJCFE
+                VVIVCCJJEE
+                VVIIICJJEE
+                MIIIIIJJEE
+                MIIISIJEEE
+                MMMISSJEEE"
+            ),
+            (1930, 1206)
+        );
+        assert_eq!(prices("EEEEE\nEXXXX\nEEEEE\nEXXXX\nEEEEE").1, 236);
+        assert_eq!(
+            prices("AAAAAA\nAAABBA\nAAABBA\nABBAAA\nABBAAA\nAAAAAA").1,
+            368
+        );
+    }
+
+    #[test]
+    fn stats_test() {
+        let g = char_grid("AAAA\nBBCD\nBBCC\nEEEC");
+        let c = components(&g, |a, b| a == b, Neighborhood::Four);
+        assert_eq!(c.regions.len(), 5);
+
+        let r = c.region_of(Point::new(3, 3)).unwrap();
+        assert_eq!(r.start, Point::new(2, 1));
+        assert_eq!((r.area, r.perimeter, r.sides), (4, 10, 8));
+        assert_eq!(r.bounds, Rect::new(2, 1, 2, 3));
+        assert!(r.touches_border);
+        assert_eq!(c.cells(c.label(Point::new(3, 1)).unwrap()).count(), 1);
+
+        let g = char_grid("....\n.##.\n.##.\n....");
+        let c = components(&g, |a, b| a == b, Neighborhood::Four);
+        assert!(!c.region_of(Point::new(1, 1)).unwrap().touches_border);
+    }
+
+    #[test]
+    fn connectivity_test() {
+        let g = char_grid("#..\n.#.\n..#");
+        let four = components(&g, |a, b| a == b, Neighborhood::Four);
+        let eight = components(&g, |a, b| a == b, Neighborhood::Eight);
+        // The dots split into two corners with 4-connectivity, and connect
+        // across the diagonal with 8.
+        assert_eq!(four.regions.len(), 5);
+        assert_eq!(eight.regions.len(), 2);
+
+        let diag = eight.region_of(Point::new(1, 1)).unwrap();
+        assert_eq!((diag.area, diag.perimeter, diag.sides), (3, 12, 12));
+        assert_eq!(diag.bounds, Rect::new(0, 0, 3, 3));
+    }
+
+    #[test]
+    fn toroidal_test() {
+        let g = char_grid("#..#\n#..#\n....\n....");
+        let c = components(&g, |a, b| a == b, Neighborhood::Four);
+        assert_eq!(c.regions.len(), 3);
+
+        // The two halves of the walls meet across the left and right edges.
+        let g = g.with_topology(Topology::Toroidal);
+        let c = components(&g, |a, b| a == b, Neighborhood::Four);
+        assert_eq!(c.regions.len(), 2);
+        assert_eq!(c.label(Point::new(0, 0)), c.label(Point::new(3, 1)));
+        let walls = c.region_of(Point::new(0, 0)).unwrap();
+        assert_eq!((walls.area, walls.perimeter, walls.sides), (4, 8, 4));
+        let dots = c.region_of(Point::new(1, 0)).unwrap();
+        assert_eq!((dots.area, dots.perimeter), (12, 8));
+    }
+
+    #[test]
+    fn large_region_test() {
+        // Deep enough to overflow the stack with a recursive flood fill.
+        let g = Grid::from_fn(500, 500, |_| 0u8);
+        let c = components(&g, |a, b| a == b, Neighborhood::Four);
+        assert_eq!(c.regions.len(), 1);
+        assert_eq!(c.regions[0].perimeter, 2000);
+        assert_eq!(c.regions[0].sides, 4);
+    }
+}
@@ -0,0 +1,250 @@
+// Breadth first distance maps: the number of steps from the nearest of a set
+// of sources to every cell of a grid.
+//
+// Movement is between adjacent passable cells, either orthogonally or also
+// diagonally, and follows the grid's topology. Sources are always reachable
+// at distance 0, even if they are not passable themselves.
+
+use std::collections::VecDeque;
+
+use super::{Grid, Point};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Neighborhood {
+    #[default]
+    Four,
+    Eight,
+}
+
+impl Neighborhood {
+    // The cells next to p, following grid's topology and skipping points
+    // outside of it.
+    pub fn neighbors<T>(self, grid: &Grid<T>, p: Point) -> impl Iterator<Item = Point> {
+        let (four, eight) = match self {
+            Neighborhood::Four => (Some(grid.neighbors(p)), None),
+            Neighborhood::Eight => (None, Some(grid.neighbors8(p))),
+        };
+        four.into_iter()
+            .flatten()
+            .chain(eight.into_iter().flatten())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DistanceMap {
+    dist: Grid<Option<u32>>,
+    neighborhood: Neighborhood,
+}
+
+impl DistanceMap {
+    pub fn new<T>(
+        grid: &Grid<T>,
+        sources: impl IntoIterator<Item = Point>,
+        passable: impl Fn(&T) -> bool,
+        neighborhood: Neighborhood,
+    ) -> Self {
+        let mut dist =
+            Grid::from_fn(grid.width(), grid.height(), |_| None).with_topology(grid.topology());
+        let mut queue = VecDeque::new();
+        for s in sources {
+            if let Some(d @ None) = dist.get_mut(s) {
+                *d = Some(0);
+                queue.push_back(s);
+            }
+        }
+
+        while let Some(cur) = queue.pop_front() {
+            let next_dist = dist[cur].unwrap() + 1;
+            for next in neighborhood.neighbors(grid, cur) {
+                if !passable(&grid[next]) {
+                    continue;
+                }
+                let d = &mut dist[next];
+                if d.is_none() {
+                    *d = Some(next_dist);
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        Self { dist, neighborhood }
+    }
+
+    // None if p is unreachable or outside the grid.
+    pub fn get(&self, p: Point) -> Option<u32> {
+        self.dist.get(p).copied().flatten()
+    }
+
+    pub fn grid(&self) -> &Grid<Option<u32>> {
+        &self.dist
+    }
+
+    pub fn into_grid(self) -> Grid<Option<u32>> {
+        self.dist
+    }
+
+    // A shortest path from the nearest source to p, inclusive. Each step
+    // goes to the first neighbour one closer to a source, in the order of
+    // Direction::iter, or Direction8::iter for Neighborhood::Eight.
+    pub fn path_to(&self, p: Point) -> Option<Vec<Point>> {
+        let mut d = self.get(p)?;
+        let mut ret = vec![p];
+        let mut cur = p;
+        while d > 0 {
+            cur = self
+                .neighborhood
+                .neighbors(&self.dist, cur)
+                .find(|&n| self.get(n) == Some(d - 1))
+                .unwrap();
+            ret.push(cur);
+            d -= 1;
+        }
+        ret.reverse();
+        Some(ret)
+    }
+
+    // The reachable cells at most budget steps from a source, in row-major
+    // order.
+    pub fn within(&self, budget: u32) -> impl Iterator<Item = (Point, u32)> + '_ {
+        self.dist
+            .iter_items()
+            .filter_map(move |(p, d)| d.filter(|&d| d <= budget).map(|d| (p, d)))
+    }
+}
+
+// A shortest path from start to end, inclusive. None if end is unreachable.
+pub fn shortest_path<T>(
+    grid: &Grid<T>,
+    start: Point,
+    end: Point,
+    passable: impl Fn(&T) -> bool,
+    neighborhood: Neighborhood,
+) -> Option<Vec<Point>> {
+    DistanceMap::new(grid, [start], passable, neighborhood).path_to(end)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    use super::*;
+    use crate::grid::Topology;
+
+    // Bellman-Ford style relaxation until nothing changes.
+    fn brute_force(
+        grid: &Grid<bool>,
+        sources: &[Point],
+        neighborhood: Neighborhood,
+    ) -> Grid<Option<u32>> {
+        let mut dist = Grid::from_fn(grid.width(), grid.height(), |p| {
+            sources.contains(&p).then_some(0)
+        });
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for p in grid.iter_points() {
+                if !grid[p] || sources.contains(&p) {
+                    continue;
+                }
+                let best = neighborhood
+                    .neighbors(grid, p)
+                    .filter_map(|n| dist.get(n).copied().flatten())
+                    .min()
+                    .map(|d| d + 1);
+                if best.is_some() && (dist[p].is_none() || best < dist[p]) {
+                    dist[p] = best;
+                    changed = true;
+                }
+            }
+        }
+        dist
+    }
+
+    #[test]
+    fn distance_map_test() {
+        // true is passable.
+        let g = Grid::new(vec![
+            vec![true, true, true],
+            vec![false, false, true],
+            vec![true, true, true],
+            vec![true, false, false],
+        ]);
+        let m = DistanceMap::new(&g, [Point::new(0, 0)], |&c| c, Neighborhood::Four);
+        assert_eq!(m.get(Point::new(0, 3)), Some(7));
+        assert_eq!(m.get(Point::new(2, 3)), None);
+        assert_eq!(m.within(2).count(), 3);
+        assert_eq!(
+            m.path_to(Point::new(0, 2)).unwrap(),
+            vec![
+                Point::new(0, 0),
+                Point::new(1, 0),
+                Point::new(2, 0),
+                Point::new(2, 1),
+                Point::new(2, 2),
+                Point::new(1, 2),
+                Point::new(0, 2),
+            ]
+        );
+
+        let m = DistanceMap::new(&g, [Point::new(0, 0)], |&c| c, Neighborhood::Eight);
+        assert_eq!(m.get(Point::new(0, 2)), Some(4));
+        let path = shortest_path(
+            &g,
+            Point::new(0, 3),
+            Point::new(0, 0),
+            |&c| c,
+            Neighborhood::Four,
+        );
+        assert_eq!(path.map(|p| p.len()), Some(8));
+        let path = shortest_path(
+            &g,
+            Point::new(0, 0),
+            Point::new(2, 3),
+            |&c| c,
+            Neighborhood::Four,
+        );
+        assert_eq!(path, None);
+    }
+
+    #[test]
+    fn toroidal_test() {
+        let g = Grid::new(vec![vec![true; 5]; 2]).with_topology(Topology::Toroidal);
+        let m = DistanceMap::new(&g, [Point::new(0, 0)], |&c| c, Neighborhood::Four);
+        assert_eq!(m.get(Point::new(4, 0)), Some(1));
+        assert_eq!(m.get(Point::new(3, 1)), Some(3));
+        assert_eq!(
+            m.path_to(Point::new(4, 1)).unwrap(),
+            vec![Point::new(0, 0), Point::new(4, 0), Point::new(4, 1)]
+        );
+    }
+
+    #[test]
+    fn matches_brute_force_test() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..50 {
+            let (w, h) = (rng.random_range(1..12), rng.random_range(1..12));
+            let g = Grid::from_fn(w, h, |_| rng.random_bool(0.7));
+            let sources: Vec<_> = (0..rng.random_range(1..4))
+                .map(|_| Point::new(rng.random_range(0..w), rng.random_range(0..h)))
+                .collect();
+
+            for n in [Neighborhood::Four, Neighborhood::Eight] {
+                let m = DistanceMap::new(&g, sources.iter().copied(), |&c| c, n);
+                assert_eq!(m.grid(), &brute_force(&g, &sources, n));
+
+                for p in g.iter_points() {
+                    let Some(path) = m.path_to(p) else {
+                        continue;
+                    };
+                    assert_eq!(path.len() as u32, m.get(p).unwrap() + 1);
+                    assert!(sources.contains(&path[0]));
+                    assert!(path[1..].iter().all(|&q| g[q]));
+                    assert!(
+                        path.windows(2)
+                            .all(|w| n.neighbors(&g, w[0]).any(|q| q == w[1]))
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,308 @@
+// Image export of grid states, for simulations where text output is too
+// large to inspect.
+//
+// A Palette maps cells to colors to build a Frame. Simulations report their
+// state after each step to a Recorder, which keeps every nth frame and saves
+// them as an animated GIF or a directory of numbered PNG/PPM images.
+
+use std::{
+    borrow::Cow,
+    fs,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use ahash::AHashMap;
+use anyhow::{Context, Result, bail};
+
+use super::{Grid, Point};
+
+pub type Rgb = [u8; 3];
+
+pub trait Palette<T> {
+    fn color(&self, cell: &T) -> Rgb;
+}
+
+impl<T, F: Fn(&T) -> Rgb> Palette<T> for F {
+    fn color(&self, cell: &T) -> Rgb {
+        self(cell)
+    }
+}
+
+// An RGB image with one pixel per grid cell until it is scaled.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frame {
+    width: usize,
+    height: usize,
+    pixels: Vec<Rgb>,
+}
+
+impl Frame {
+    pub fn new(width: usize, height: usize, fill: Rgb) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![fill; width * height],
+        }
+    }
+
+    pub fn from_grid<T>(grid: &Grid<T>, palette: &impl Palette<T>) -> Self {
+        let (width, height) = grid.size();
+        Self {
+            width,
+            height,
+            pixels: grid.cells().iter().map(|c| palette.color(c)).collect(),
+        }
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    pub fn get(&self, p: Point) -> Option<Rgb> {
+        (p.x < self.width && p.y < self.height).then(|| self.pixels[p.y * self.width + p.x])
+    }
+
+    // Colors the pixel at p. Points outside the frame are ignored.
+    pub fn paint(&mut self, p: Point, color: Rgb) {
+        if p.x < self.width && p.y < self.height {
+            self.pixels[p.y * self.width + p.x] = color;
+        }
+    }
+
+    // Returns the frame with every pixel drawn as a factor x factor square.
+    pub fn scaled(&self, factor: usize) -> Frame {
+        let width = self.width * factor;
+        let mut pixels = Vec::with_capacity(width * self.height * factor);
+        for row in self.pixels.chunks(self.width.max(1)) {
+            let line: Vec<Rgb> = row
+                .iter()
+                .flat_map(|&c| std::iter::repeat_n(c, factor))
+                .collect();
+            for _ in 0..factor {
+                pixels.extend_from_slice(&line);
+            }
+        }
+
+        Frame {
+            width,
+            height: self.height * factor,
+            pixels,
+        }
+    }
+
+    pub fn write_ppm(&self, mut w: impl Write) -> Result<()> {
+        write!(w, "P6\n{} {}\n255\n", self.width, self.height)?;
+        w.write_all(self.pixels.as_flattened())?;
+        Ok(())
+    }
+
+    pub fn write_png(&self, w: impl Write) -> Result<()> {
+        let mut encoder = png::Encoder::new(w, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(self.pixels.as_flattened())?;
+        writer.finish()?;
+        Ok(())
+    }
+
+    pub fn write(&self, w: impl Write, format: ImageFormat) -> Result<()> {
+        match format {
+            ImageFormat::Ppm => self.write_ppm(w),
+            ImageFormat::Png => self.write_png(w),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    Ppm,
+    Png,
+}
+
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Ppm => "ppm",
+            ImageFormat::Png => "png",
+        }
+    }
+}
+
+// Writes frames as a looping GIF, scaling each frame as it is written.
+// delay is in hundredths of a second. GIF frames are indexed, so the frames
+// may use at most 256 distinct colors.
+pub fn write_gif(frames: &[Frame], scale: usize, delay: u16, w: impl Write) -> Result<()> {
+    let Some(first) = frames.first() else {
+        bail!("no frames to write");
+    };
+    if frames.iter().any(|f| f.size() != first.size()) {
+        bail!("frames have different sizes");
+    }
+    let (Ok(width), Ok(height)) = (
+        u16::try_from(first.width * scale),
+        u16::try_from(first.height * scale),
+    ) else {
+        bail!("frame is too large for a gif: {:?}", first.size());
+    };
+
+    let mut palette: Vec<Rgb> = Vec::new();
+    let mut index: AHashMap<Rgb, u8> = AHashMap::new();
+    for &c in frames.iter().flat_map(|f| &f.pixels) {
+        if !index.contains_key(&c) {
+            let Ok(i) = u8::try_from(palette.len()) else {
+                bail!("frames use more than 256 colors");
+            };
+            palette.push(c);
+            index.insert(c, i);
+        }
+    }
+
+    let mut encoder = gif::Encoder::new(w, width, height, palette.as_flattened())?;
+    encoder.set_repeat(gif::Repeat::Infinite)?;
+    for frame in frames {
+        let buf = frame
+            .scaled(scale)
+            .pixels
+            .iter()
+            .map(|c| index[c])
+            .collect();
+        encoder.write_frame(&gif::Frame {
+            width,
+            height,
+            delay,
+            buffer: Cow::Owned(buf),
+            ..gif::Frame::default()
+        })?;
+    }
+
+    Ok(())
+}
+
+// Collects frames from a simulation. Simulations call step once per step;
+// only every nth frame is built and kept.
+pub struct Recorder {
+    every: usize,
+    steps: usize,
+    frames: Vec<Frame>,
+}
+
+impl Recorder {
+    pub fn new(every: usize) -> Self {
+        Self {
+            every: every.max(1),
+            steps: 0,
+            frames: Vec::new(),
+        }
+    }
+
+    // The step-callback hook. make is only called for kept steps.
+    pub fn step(&mut self, make: impl FnOnce() -> Frame) {
+        if self.steps.is_multiple_of(self.every) {
+            self.frames.push(make());
+        }
+        self.steps += 1;
+    }
+
+    // Keeps frame regardless of the sampling, e.g. for the final state.
+    pub fn push(&mut self, frame: Frame) {
+        self.frames.push(frame);
+    }
+
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    pub fn save_gif(&self, path: &Path, scale: usize, delay: u16) -> Result<()> {
+        let f = fs::File::create(path)
+            .with_context(|| format!("failed to create {}", path.display()))?;
+        write_gif(&self.frames, scale, delay, BufWriter::new(f))
+    }
+
+    // Writes frame-00000.png, frame-00001.png, ... to dir.
+    pub fn save_dir(&self, dir: &Path, scale: usize, format: ImageFormat) -> Result<()> {
+        fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+        for (i, frame) in self.frames.iter().enumerate() {
+            let path = dir.join(format!("frame-{:05}.{}", i, format.extension()));
+            let f = fs::File::create(&path)
+                .with_context(|| format!("failed to create {}", path.display()))?;
+            frame.scaled(scale).write(BufWriter::new(f), format)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: Rgb = [0, 0, 0];
+    const WHITE: Rgb = [255, 255, 255];
+
+    fn test_frame() -> Frame {
+        let grid = Grid::new(vec![vec![0u8, 1], vec![1, 0]]);
+        Frame::from_grid(&grid, &|c: &u8| if *c == 0 { BLACK } else { WHITE })
+    }
+
+    #[test]
+    fn frame_test() {
+        let mut f = test_frame().scaled(2);
+        assert_eq!(f.size(), (4, 4));
+        assert_eq!(f.get(Point::new(1, 1)), Some(BLACK));
+        assert_eq!(f.get(Point::new(2, 1)), Some(WHITE));
+
+        f.paint(Point::new(3, 3), WHITE);
+        f.paint(Point::new(9, 9), WHITE);
+        assert_eq!(f.get(Point::new(3, 3)), Some(WHITE));
+        assert_eq!(f.get(Point::new(9, 9)), None);
+
+        let mut ppm = Vec::new();
+        test_frame().write_ppm(&mut ppm).unwrap();
+        assert_eq!(&ppm[..11], b"P6\n2 2\n255\n");
+        assert_eq!(
+            &ppm[11..],
+            &[0, 0, 0, 255, 255, 255, 255, 255, 255, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn png_test() {
+        let mut buf = Vec::new();
+        test_frame().write_png(&mut buf).unwrap();
+
+        let mut reader = png::Decoder::new(std::io::Cursor::new(buf))
+            .read_info()
+            .unwrap();
+        let mut data = vec![0; reader.output_buffer_size().unwrap()];
+        let info = reader.next_frame(&mut data).unwrap();
+        assert_eq!((info.width, info.height), (2, 2));
+        assert_eq!(&data[..6], &[0, 0, 0, 255, 255, 255]);
+    }
+
+    #[test]
+    fn gif_test() {
+        let mut rec = Recorder::new(2);
+        for i in 0..5u8 {
+            rec.step(|| Frame::new(3, 2, [i, i, i]));
+        }
+        assert_eq!(rec.frames().len(), 3);
+
+        let mut buf = Vec::new();
+        write_gif(rec.frames(), 2, 5, &mut buf).unwrap();
+
+        let mut decoder = gif::DecodeOptions::new().read_info(buf.as_slice()).unwrap();
+        assert_eq!((decoder.width(), decoder.height()), (6, 4));
+        let mut n = 0;
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            assert_eq!(frame.delay, 5);
+            n += 1;
+        }
+        assert_eq!(n, 3);
+
+        let many: Vec<_> = (0..300)
+            .map(|i| Frame::new(1, 1, [0, (i >> 8) as u8, i as u8]))
+            .collect();
+        assert!(write_gif(&many, 1, 1, Vec::new()).is_err());
+    }
+}
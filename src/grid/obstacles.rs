@@ -0,0 +1,146 @@
+// Sorted per-row and per-column obstacle positions, so "move in a straight
+// line until something is in the way" takes log time instead of a walk over
+// every cell.
+
+use std::collections::BTreeSet;
+
+use super::{Direction, Grid, Point};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ObstacleIndex {
+    // x -> y values that contain obstacles.
+    x_index: Vec<BTreeSet<usize>>,
+    // y -> x values that contain obstacles.
+    y_index: Vec<BTreeSet<usize>>,
+}
+
+impl ObstacleIndex {
+    // An index with no obstacles.
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            x_index: vec![BTreeSet::new(); width],
+            y_index: vec![BTreeSet::new(); height],
+        }
+    }
+
+    // Indexes every cell of grid for which is_obstacle is true.
+    pub fn from_grid<T>(grid: &Grid<T>, is_obstacle: impl Fn(&T) -> bool) -> Self {
+        let (width, height) = grid.size();
+        let mut ret = Self::new(width, height);
+        for (p, v) in grid.iter_items() {
+            if is_obstacle(v) {
+                ret.insert(p);
+            }
+        }
+        ret
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        (self.x_index.len(), self.y_index.len())
+    }
+
+    pub fn contains(&self, p: Point) -> bool {
+        self.y_index.get(p.y).is_some_and(|xs| xs.contains(&p.x))
+    }
+
+    // Returns true if p was not an obstacle before. Panics if p is outside
+    // the index.
+    pub fn insert(&mut self, p: Point) -> bool {
+        self.x_index[p.x].insert(p.y);
+        self.y_index[p.y].insert(p.x)
+    }
+
+    // Returns true if p was an obstacle.
+    pub fn remove(&mut self, p: Point) -> bool {
+        if !self.contains(p) {
+            return false;
+        }
+        self.x_index[p.x].remove(&p.y);
+        self.y_index[p.y].remove(&p.x)
+    }
+
+    // The first obstacle strictly after pos in direction dir.
+    pub fn next_obstacle(&self, pos: Point, dir: Direction) -> Option<Point> {
+        match dir {
+            Direction::South => self
+                .x_index
+                .get(pos.x)?
+                .range(pos.y + 1..)
+                .next()
+                .map(|&y| Point::new(pos.x, y)),
+            Direction::North => self
+                .x_index
+                .get(pos.x)?
+                .range(..pos.y)
+                .next_back()
+                .map(|&y| Point::new(pos.x, y)),
+            Direction::East => self
+                .y_index
+                .get(pos.y)?
+                .range(pos.x + 1..)
+                .next()
+                .map(|&x| Point::new(x, pos.y)),
+            Direction::West => self
+                .y_index
+                .get(pos.y)?
+                .range(..pos.x)
+                .next_back()
+                .map(|&x| Point::new(x, pos.y)),
+        }
+    }
+
+    // The position just before the next obstacle in direction dir, where
+    // something sliding from pos comes to rest. None if nothing is in the
+    // way.
+    pub fn slide(&self, pos: Point, dir: Direction) -> Option<Point> {
+        self.next_obstacle(pos, dir)?.next(dir.opposite())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn obstacle_index_test() {
+        // 1 is an obstacle.
+        let g = Grid::new(vec![
+            vec![0u8, 1, 0, 0, 0],
+            vec![0, 0, 0, 0, 1],
+            vec![1, 0, 0, 0, 0],
+            vec![0, 0, 0, 1, 0],
+        ]);
+        let mut idx = ObstacleIndex::from_grid(&g, |&c| c == 1);
+        let p = Point::new(1, 2);
+
+        assert_eq!(
+            idx.next_obstacle(p, Direction::North),
+            Some(Point::new(1, 0))
+        );
+        assert_eq!(idx.slide(p, Direction::North), Some(Point::new(1, 1)));
+        assert_eq!(
+            idx.next_obstacle(p, Direction::West),
+            Some(Point::new(0, 2))
+        );
+        assert_eq!(idx.slide(p, Direction::West), Some(p));
+        assert_eq!(idx.next_obstacle(p, Direction::East), None);
+        assert_eq!(idx.next_obstacle(p, Direction::South), None);
+
+        let q = Point::new(3, 0);
+        assert_eq!(idx.slide(q, Direction::South), Some(Point::new(3, 2)));
+        assert_eq!(
+            idx.slide(Point::new(0, 1), Direction::East),
+            Some(Point::new(3, 1))
+        );
+        // Obstacles at pos itself are ignored.
+        assert_eq!(idx.next_obstacle(Point::new(4, 1), Direction::East), None);
+
+        assert!(idx.insert(Point::new(1, 3)));
+        assert!(!idx.insert(Point::new(1, 3)));
+        assert_eq!(idx.slide(p, Direction::South), Some(p));
+        assert!(idx.remove(Point::new(1, 0)));
+        assert!(!idx.remove(Point::new(1, 0)));
+        assert_eq!(idx.next_obstacle(p, Direction::North), None);
+        assert!(!idx.contains(Point::new(9, 9)));
+    }
+}
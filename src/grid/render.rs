@@ -0,0 +1,175 @@
+// Text rendering of grids for debugging.
+//
+// Cells are drawn with FromGridChar::to_char. Overlays replace the glyph of
+// individual points, e.g. to draw a path over a maze; an overlay added later
+// is drawn on top of earlier ones. Rulers label rows and columns, and crop
+// limits the output to a window of a large grid.
+
+use std::{fmt, ops::Deref};
+
+use ahash::AHashMap;
+
+use super::{FromGridChar, Grid, Point, Rect, Vector};
+
+pub struct Render<'a, T> {
+    grid: Source<'a, T>,
+    overlay: AHashMap<Point, char>,
+    rulers: bool,
+    window: Rect,
+    // The coordinate that rulers show for point (0, 0).
+    origin: Vector,
+}
+
+// Grids converted for rendering, e.g. from a SparseGrid, are owned by the
+// Render.
+enum Source<'a, T> {
+    Borrowed(&'a Grid<T>),
+    Owned(Grid<T>),
+}
+
+impl<T> Deref for Source<'_, T> {
+    type Target = Grid<T>;
+
+    fn deref(&self) -> &Grid<T> {
+        match self {
+            Source::Borrowed(g) => g,
+            Source::Owned(g) => g,
+        }
+    }
+}
+
+impl<'a, T> Render<'a, T> {
+    pub fn new(grid: &'a Grid<T>) -> Self {
+        Self::from_source(Source::Borrowed(grid))
+    }
+
+    pub(super) fn owned(grid: Grid<T>) -> Self {
+        Self::from_source(Source::Owned(grid))
+    }
+
+    fn from_source(grid: Source<'a, T>) -> Self {
+        Self {
+            window: grid.bounds(),
+            grid,
+            overlay: AHashMap::new(),
+            rulers: false,
+            origin: Vector::ZERO,
+        }
+    }
+
+    // Draws glyph at each of points instead of the cell.
+    pub fn overlay(mut self, glyph: char, points: impl IntoIterator<Item = Point>) -> Self {
+        self.overlay.extend(points.into_iter().map(|p| (p, glyph)));
+        self
+    }
+
+    // Labels rows on the left and columns on top, one digit per line.
+    pub fn rulers(mut self, on: bool) -> Self {
+        self.rulers = on;
+        self
+    }
+
+    // Only draws the part of the grid inside window.
+    pub fn crop(mut self, window: Rect) -> Self {
+        self.window = self.grid.bounds().intersect(window);
+        self
+    }
+
+    // Labels point (0, 0) as origin in the rulers.
+    pub(super) fn origin(mut self, origin: Vector) -> Self {
+        self.origin = origin;
+        self
+    }
+}
+
+impl<T: FromGridChar> fmt::Display for Render<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let w = self.window;
+        if w.is_empty() {
+            return Ok(());
+        }
+
+        let xs = w.x..w.x + w.width;
+        let ys = w.y..w.y + w.height;
+        let row_label = |y: usize| (y as i64 + self.origin.y).to_string();
+        let label_width = ys.clone().map(|y| row_label(y).len()).max().unwrap();
+
+        if self.rulers {
+            // Column labels are written top to bottom and right aligned, so
+            // there are no leading zeros.
+            let labels: Vec<Vec<char>> = xs
+                .clone()
+                .map(|x| (x as i64 + self.origin.x).to_string().chars().collect())
+                .collect();
+            let height = labels.iter().map(|l| l.len()).max().unwrap();
+            for line in 0..height {
+                let line: String = labels
+                    .iter()
+                    .map(|l| (line + l.len()).checked_sub(height).map_or(' ', |i| l[i]))
+                    .collect();
+                writeln!(f, "{:label_width$} {}", "", line)?;
+            }
+        }
+
+        for y in ys {
+            if self.rulers {
+                write!(f, "{:>label_width$} ", row_label(y))?;
+            }
+            let line: String = xs
+                .clone()
+                .map(|x| {
+                    let p = Point::new(x, y);
+                    self.overlay
+                        .get(&p)
+                        .copied()
+                        .unwrap_or_else(|| self.grid[p].to_char())
+                })
+                .collect();
+            writeln!(f, "{}", line)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_grid() -> Grid<u8> {
+        let rows = (0..12)
+            .map(|y| (0..12).map(|x| ((x + y) % 10) as u8).collect())
+            .collect();
+        Grid::new(rows)
+    }
+
+    #[test]
+    fn overlay_test() {
+        let g = Grid::new(vec![vec![0u8, 1, 2], vec![3, 4, 5]]);
+        let out = g
+            .render()
+            .overlay('.', g.iter_points())
+            .overlay('#', [Point::new(1, 0), Point::new(2, 1)])
+            .to_string();
+        assert_eq!(out, ".#.\n..#\n");
+    }
+
+    #[test]
+    fn crop_rulers_test() {
+        let g = test_grid();
+        let out = g
+            .render()
+            .crop(Rect::new(8, 9, 3, 2))
+            .rulers(true)
+            .to_string();
+        assert_eq!(out, "     1\n   890\n 9 789\n10 890\n");
+
+        // Windows are clipped to the grid.
+        let out = g
+            .render()
+            .crop(Rect::around(Point::new(11, 0), 1))
+            .to_string();
+        assert_eq!(out, "01\n12\n");
+        assert_eq!(g.render().crop(Rect::new(20, 20, 5, 5)).to_string(), "");
+    }
+}
@@ -0,0 +1,336 @@
+// An unbounded grid keyed by signed coordinates, for puzzles on an infinite
+// plane or whose coordinates go negative.
+//
+// Only cells that differ from the default value are stored. The bounding box
+// covers exactly the stored cells.
+
+use std::fmt;
+
+use ahash::AHashMap;
+
+use super::{Direction, Direction8, FromGridChar, Grid, Point, Rect, Render, Vector};
+
+// The inclusive range of coordinates from min to max.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BoundingBox {
+    pub min: Vector,
+    pub max: Vector,
+}
+
+impl BoundingBox {
+    pub fn new(p: Vector) -> Self {
+        Self { min: p, max: p }
+    }
+
+    pub fn width(&self) -> usize {
+        (self.max.x - self.min.x + 1) as usize
+    }
+
+    pub fn height(&self) -> usize {
+        (self.max.y - self.min.y + 1) as usize
+    }
+
+    pub fn contains(&self, p: Vector) -> bool {
+        (self.min.x..=self.max.x).contains(&p.x) && (self.min.y..=self.max.y).contains(&p.y)
+    }
+
+    // Grows the box to include p.
+    pub fn extend(&mut self, p: Vector) {
+        self.min = Vector::new(self.min.x.min(p.x), self.min.y.min(p.y));
+        self.max = Vector::new(self.max.x.max(p.x), self.max.y.max(p.y));
+    }
+
+    fn on_edge(&self, p: Vector) -> bool {
+        p.x == self.min.x || p.x == self.max.x || p.y == self.min.y || p.y == self.max.y
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SparseGrid<T> {
+    cells: AHashMap<Vector, T>,
+    default: T,
+    bounds: Option<BoundingBox>,
+}
+
+impl<T: Clone + PartialEq> SparseGrid<T> {
+    pub fn new(default: T) -> Self {
+        Self {
+            cells: AHashMap::new(),
+            default,
+            bounds: None,
+        }
+    }
+
+    // Stores every cell of grid that is not default. Point (x, y) of the
+    // grid becomes Vector (x, y).
+    pub fn from_grid(grid: &Grid<T>, default: T) -> Self {
+        let mut ret = Self::new(default);
+        for (p, v) in grid.iter_items() {
+            ret.set(Vector::new(p.x as i64, p.y as i64), v.clone());
+        }
+        ret
+    }
+
+    pub fn get(&self, p: Vector) -> &T {
+        self.cells.get(&p).unwrap_or(&self.default)
+    }
+
+    // Setting a cell to the default value removes it.
+    pub fn set(&mut self, p: Vector, v: T) {
+        if v == self.default {
+            self.remove(p);
+            return;
+        }
+
+        self.cells.insert(p, v);
+        match &mut self.bounds {
+            Some(b) => b.extend(p),
+            None => self.bounds = Some(BoundingBox::new(p)),
+        }
+    }
+
+    pub fn remove(&mut self, p: Vector) -> Option<T> {
+        let ret = self.cells.remove(&p)?;
+        // Only a cell on the edge of the box can shrink it.
+        if self.bounds.is_some_and(|b| b.on_edge(p)) {
+            self.bounds = self.compute_bounds();
+        }
+        Some(ret)
+    }
+
+    fn compute_bounds(&self) -> Option<BoundingBox> {
+        let mut keys = self.cells.keys();
+        let mut b = BoundingBox::new(*keys.next()?);
+        keys.for_each(|&p| b.extend(p));
+        Some(b)
+    }
+
+    // The smallest box containing every stored cell. None if the grid is
+    // all default.
+    pub fn bounds(&self) -> Option<BoundingBox> {
+        self.bounds
+    }
+
+    pub fn default_value(&self) -> &T {
+        &self.default
+    }
+
+    // The number of stored cells.
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    // The stored cells in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (Vector, &T)> {
+        self.cells.iter().map(|(&p, v)| (p, v))
+    }
+
+    pub fn neighbors(&self, p: Vector) -> impl Iterator<Item = Vector> + use<T> {
+        Direction::iter().map(move |d| p + Vector::from(d))
+    }
+
+    pub fn neighbors8(&self, p: Vector) -> impl Iterator<Item = Vector> + use<T> {
+        Direction8::iter().map(move |d| p + d.vector())
+    }
+
+    // Walks from start in direction d until the line leaves the bounding
+    // box. Empty if start is outside of it.
+    pub fn iter_line(&self, start: Vector, d: Direction8) -> impl Iterator<Item = (Vector, &T)> {
+        let bounds = self.bounds;
+        std::iter::successors(Some(start), move |&p| Some(p + d.vector()))
+            .take_while(move |&p| bounds.is_some_and(|b| b.contains(p)))
+            .map(|p| (p, self.get(p)))
+    }
+
+    // Copies the bounding box into a dense grid. Also returns the coordinate
+    // of the grid's point (0, 0). None if the grid is all default.
+    pub fn to_grid(&self) -> Option<(Grid<T>, Vector)> {
+        let b = self.bounds?;
+        let grid = Grid::from_fn(b.width(), b.height(), |p| {
+            self.get(b.min + Vector::new(p.x as i64, p.y as i64))
+                .clone()
+        });
+        Some((grid, b.min))
+    }
+
+    // Starts rendering the bounding box as text. See SparseRender.
+    pub fn render(&self) -> SparseRender<'_, T> {
+        let (render, origin) = match self.to_grid() {
+            Some((grid, origin)) => (Some(Render::owned(grid).origin(origin)), origin),
+            None => (None, Vector::ZERO),
+        };
+        SparseRender { render, origin }
+    }
+}
+
+// Render for the bounding box of a SparseGrid. Overlays and crop windows are
+// in the sparse grid's coordinates, and the rulers show them.
+pub struct SparseRender<'a, T> {
+    // None if the grid is all default.
+    render: Option<Render<'a, T>>,
+    // The coordinate of the rendered grid's point (0, 0).
+    origin: Vector,
+}
+
+impl<T> SparseRender<'_, T> {
+    // Draws glyph at each of points instead of the cell. Points outside the
+    // bounding box are ignored.
+    pub fn overlay(mut self, glyph: char, points: impl IntoIterator<Item = Vector>) -> Self {
+        let origin = self.origin;
+        let points = points.into_iter().filter_map(move |p| {
+            let d = p - origin;
+            Some(Point::new(d.x.try_into().ok()?, d.y.try_into().ok()?))
+        });
+        self.render = self.render.map(|r| r.overlay(glyph, points));
+        self
+    }
+
+    pub fn rulers(mut self, on: bool) -> Self {
+        self.render = self.render.map(|r| r.rulers(on));
+        self
+    }
+
+    // Only draws the part of the bounding box inside window.
+    pub fn crop(mut self, window: BoundingBox) -> Self {
+        let (min, max) = (window.min - self.origin, window.max - self.origin);
+        let rect = if max.x < 0 || max.y < 0 {
+            Rect::default()
+        } else {
+            let (x, y) = (min.x.max(0), min.y.max(0));
+            Rect::new(
+                x as usize,
+                y as usize,
+                (max.x - x + 1) as usize,
+                (max.y - y + 1) as usize,
+            )
+        };
+        self.render = self.render.map(|r| r.crop(rect));
+        self
+    }
+}
+
+impl<T: FromGridChar> fmt::Display for SparseRender<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.render {
+            Some(r) => write!(f, "{}", r),
+            None => Ok(()),
+        }
+    }
+}
+
+// Draws the bounding box like Grid's Display.
+impl<T: Clone + PartialEq + FromGridChar> fmt::Display for SparseGrid<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.to_grid() {
+            Some((grid, _)) => write!(f, "{}", grid),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // One generation of Conway's game of life.
+    fn life_step(g: &SparseGrid<bool>) -> SparseGrid<bool> {
+        let mut counts: AHashMap<Vector, usize> = AHashMap::new();
+        for (p, _) in g.iter() {
+            for n in g.neighbors8(p) {
+                *counts.entry(n).or_default() += 1;
+            }
+        }
+
+        let mut next = SparseGrid::new(false);
+        for (p, n) in counts {
+            if n == 3 || (n == 2 && *g.get(p)) {
+                next.set(p, true);
+            }
+        }
+        next
+    }
+
+    #[test]
+    fn life_test() {
+        let mut g = SparseGrid::new(false);
+        for x in -1..=1 {
+            g.set(Vector::new(x, -5), true);
+        }
+        assert_eq!(g.to_string(), "###\n");
+
+        let g = life_step(&g);
+        assert_eq!(g.to_string(), "#\n#\n#\n");
+        assert_eq!(
+            g.bounds(),
+            Some(BoundingBox {
+                min: Vector::new(0, -6),
+                max: Vector::new(0, -4)
+            })
+        );
+        assert_eq!(life_step(&life_step(&g)).to_string(), "#\n#\n#\n");
+    }
+
+    #[test]
+    fn bounds_test() {
+        let mut g = SparseGrid::new(0u8);
+        assert_eq!(g.bounds(), None);
+
+        g.set(Vector::new(-3, 2), 1);
+        g.set(Vector::new(4, -1), 2);
+        g.set(Vector::new(0, 0), 3);
+        assert_eq!(g.bounds().map(|b| (b.width(), b.height())), Some((8, 4)));
+
+        g.set(Vector::new(4, -1), 0);
+        assert_eq!(g.len(), 2);
+        assert_eq!(
+            g.bounds(),
+            Some(BoundingBox {
+                min: Vector::new(-3, 0),
+                max: Vector::new(0, 2)
+            })
+        );
+
+        let line: Vec<_> = g
+            .iter_line(Vector::new(-3, 2), Direction8::NE)
+            .map(|(_, &v)| v)
+            .collect();
+        assert_eq!(line, vec![1, 0, 0]);
+    }
+
+    #[test]
+    fn render_test() {
+        let mut g = SparseGrid::new(false);
+        g.set(Vector::new(-2, -1), true);
+        g.set(Vector::new(1, 0), true);
+
+        let out = g
+            .render()
+            .overlay('O', [Vector::new(0, 0), Vector::new(5, 5)])
+            .rulers(true)
+            .to_string();
+        assert_eq!(out, "   --  \n   2101\n-1 #...\n 0 ..O#\n");
+
+        let window = BoundingBox {
+            min: Vector::new(-1, -9),
+            max: Vector::new(0, -1),
+        };
+        assert_eq!(g.render().crop(window).to_string(), "..\n");
+        assert_eq!(SparseGrid::new(false).render().rulers(true).to_string(), "");
+    }
+
+    #[test]
+    fn dense_roundtrip_test() {
+        let dense = Grid::new(vec![vec![0u8, 1, 0], vec![2, 0, 0]]);
+        let sparse = SparseGrid::from_grid(&dense, 0);
+        assert_eq!(sparse.len(), 2);
+
+        // The all-zero last column is outside the bounding box.
+        let (grid, origin) = sparse.to_grid().unwrap();
+        assert_eq!(origin, Vector::new(0, 0));
+        assert_eq!(grid, Grid::new(vec![vec![0, 1], vec![2, 0]]));
+    }
+}
@@ -0,0 +1,136 @@
+// Borrowed rectangular views of a grid. Points passed to and returned from a
+// SubGrid are relative to its top left corner.
+
+use std::ops::Index;
+
+use super::{Grid, Point, Rect};
+
+#[derive(Clone, Copy, Debug)]
+pub struct SubGrid<'a, T> {
+    grid: &'a Grid<T>,
+    // Always inside the grid's bounds.
+    rect: Rect,
+}
+
+impl<'a, T> SubGrid<'a, T> {
+    pub(super) fn new(grid: &'a Grid<T>, rect: Rect) -> Self {
+        // An empty rect may start past the end of the grid.
+        let rect = if rect.is_empty() {
+            Rect::default()
+        } else {
+            rect
+        };
+        Self { grid, rect }
+    }
+
+    // The area of the parent grid this view covers.
+    pub fn rect(&self) -> Rect {
+        self.rect
+    }
+
+    pub fn width(&self) -> usize {
+        self.rect.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.rect.height
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        (self.rect.width, self.rect.height)
+    }
+
+    pub fn contains(&self, p: Point) -> bool {
+        p.x < self.rect.width && p.y < self.rect.height
+    }
+
+    // Converts a point in the view to a point in the parent grid.
+    pub fn to_parent(&self, p: Point) -> Point {
+        Point::new(p.x + self.rect.x, p.y + self.rect.y)
+    }
+
+    pub fn get(&self, p: Point) -> Option<&'a T> {
+        if self.contains(p) {
+            self.grid.get(self.to_parent(p))
+        } else {
+            None
+        }
+    }
+
+    // Each row of the view is a contiguous slice of its parent's row.
+    pub fn rows(&self) -> impl Iterator<Item = &'a [T]> + use<'a, T> {
+        let grid = self.grid;
+        let Rect {
+            x,
+            y,
+            width,
+            height,
+        } = self.rect;
+        (y..y + height).map(move |y| &grid.cells[y * grid.width + x..][..width])
+    }
+
+    pub fn iter_items(&self) -> impl Iterator<Item = (Point, &'a T)> + use<'a, T> {
+        self.rows().enumerate().flat_map(|(y, row)| {
+            row.iter()
+                .enumerate()
+                .map(move |(x, v)| (Point::new(x, y), v))
+        })
+    }
+
+    // Copies the view into a new grid. None if the view is empty.
+    pub fn to_grid(&self) -> Option<Grid<T>>
+    where
+        T: Clone,
+    {
+        let rows = self.rows().map(|r| r.to_vec()).collect();
+        Grid::try_new(rows).ok()
+    }
+}
+
+impl<T> Index<Point> for SubGrid<'_, T> {
+    type Output = T;
+
+    fn index(&self, p: Point) -> &T {
+        self.get(p).expect("point outside of sub-grid")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_grid() -> Grid<u8> {
+        Grid::from_fn(4, 3, |p| (p.y * 4 + p.x) as u8)
+    }
+
+    #[test]
+    fn view_test() {
+        let g = test_grid();
+        let v = g.view(Rect::new(1, 1, 2, 5));
+        assert_eq!(v.size(), (2, 2));
+        assert_eq!(v[Point::new(0, 0)], 5);
+        assert_eq!(v.get(Point::new(1, 1)), Some(&10));
+        assert_eq!(v.get(Point::new(2, 0)), None);
+        assert_eq!(v.to_parent(Point::new(1, 0)), Point::new(2, 1));
+        assert!(v.rows().eq([&[5u8, 6][..], &[9, 10][..]]));
+        assert_eq!(v.iter_items().last(), Some((Point::new(1, 1), &10)));
+
+        assert!(g.view(Rect::new(4, 0, 1, 1)).to_grid().is_none());
+        assert_eq!(
+            g.crop(Rect::new(2, 0, 9, 9)),
+            Some(Grid::new(vec![vec![2, 3], vec![6, 7], vec![10, 11]]))
+        );
+    }
+
+    #[test]
+    fn windows_test() {
+        let g = test_grid();
+        let corners: Vec<_> = g.windows(3, 2).map(|w| w[Point::new(0, 0)]).collect();
+        assert_eq!(corners, vec![0, 1, 4, 5]);
+        assert!(g.windows(3, 2).all(|w| w.size() == (3, 2)));
+
+        assert_eq!(g.windows(4, 3).count(), 1);
+        assert_eq!(g.windows(5, 1).count(), 0);
+        assert_eq!(g.windows(0, 1).count(), 0);
+    }
+}
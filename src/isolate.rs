@@ -0,0 +1,324 @@
+// Runs problems in child processes of the current binary so a panic (which
+// aborts the process in release builds) or a runaway allocation only takes
+// down a single problem.
+//
+// The child is started as `run-child <day> <problem>` and reports exactly one
+// record on stdout: a status line ("ok <nanos> [<allocs> <bytes> <peak>]",
+// "error" or "panic") followed by the answer or message.
+
+use std::{
+    fmt,
+    io::{Read, Write},
+    process::{Child, Command, ExitStatus, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result, anyhow};
+
+use crate::Solved;
+use crate::alloc_stats::AllocStats;
+use crate::cancel::TimedOut;
+use crate::parallel;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Limits {
+    // Maximum address space of the child in MiB.
+    pub mem_mib: Option<u64>,
+    // Maximum CPU time of the child in seconds.
+    pub cpu_secs: Option<u64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Panicked(pub String);
+
+impl fmt::Display for Panicked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "panicked: {}", self.0)
+    }
+}
+
+impl std::error::Error for Panicked {}
+
+// Runs day/problem in a child process and collects its result.
+pub fn run_problem(
+    day: usize,
+    problem: usize,
+    timeout: Option<Duration>,
+    limits: Limits,
+) -> Result<Solved> {
+    let exe = std::env::current_exe().context("failed to find current executable")?;
+    let mut cmd = Command::new(exe);
+    cmd.arg("run-child")
+        .arg(day.to_string())
+        .arg(problem.to_string());
+    if let Some(mib) = limits.mem_mib {
+        cmd.arg("--mem-limit").arg(mib.to_string());
+    }
+    if let Some(secs) = limits.cpu_secs {
+        cmd.arg("--cpu-limit").arg(secs.to_string());
+    }
+    if let Some(n) = parallel::threads() {
+        cmd.arg("--threads").arg(n.to_string());
+    }
+    if !parallel::enabled() {
+        cmd.arg("--sequential");
+    }
+
+    run_command(cmd, timeout)
+}
+
+fn run_command(mut cmd: Command, timeout: Option<Duration>) -> Result<Solved> {
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("failed to start child process")?;
+
+    let stdout = read_in_background(child.stdout.take());
+    let stderr = read_in_background(child.stderr.take());
+
+    let status = wait(&mut child, timeout)?;
+    let stdout = stdout
+        .join()
+        .map_err(|_| anyhow!("failed to read child output"))?;
+    let stderr = stderr
+        .join()
+        .map_err(|_| anyhow!("failed to read child output"))?;
+
+    parse_record(&stdout).unwrap_or_else(|| Err(crash_error(status, &stderr)))
+}
+
+fn read_in_background(pipe: Option<impl Read + Send + 'static>) -> thread::JoinHandle<String> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+        String::from_utf8_lossy(&buf).into_owned()
+    })
+}
+
+// Waits for the child to exit, killing it if it runs past timeout.
+fn wait(child: &mut Child, timeout: Option<Duration>) -> Result<ExitStatus> {
+    let Some(timeout) = timeout else {
+        return Ok(child.wait()?);
+    };
+
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(TimedOut(timeout).into());
+        }
+
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+fn parse_record(output: &str) -> Option<Result<Solved>> {
+    let (status, payload) = output.split_once('\n').unwrap_or((output, ""));
+    let mut fields = status.split(' ');
+
+    let ret = match fields.next()? {
+        "ok" => {
+            let mut nums = fields.map(|x| x.parse::<u64>());
+            let nanos = nums.next()?.ok()?;
+            let alloc = match (nums.next(), nums.next(), nums.next()) {
+                (Some(a), Some(b), Some(p)) => Some(AllocStats {
+                    allocations: a.ok()?,
+                    bytes: b.ok()?,
+                    peak_bytes: p.ok()?,
+                }),
+                _ => None,
+            };
+
+            Ok(Solved {
+                answer: payload.to_owned(),
+                duration: Duration::from_nanos(nanos),
+                alloc,
+            })
+        }
+        "error" => Err(anyhow!("{}", payload)),
+        "panic" => Err(Panicked(payload.to_owned()).into()),
+        _ => return None,
+    };
+
+    Some(ret)
+}
+
+// Describes a child that exited without reporting a record, typically
+// because it was killed by a resource limit or failed to allocate.
+fn crash_error(status: ExitStatus, stderr: &str) -> anyhow::Error {
+    let reason = stderr
+        .lines()
+        .find(|l| !l.trim().is_empty())
+        .map(|l| l.trim().to_owned())
+        .unwrap_or_else(|| describe_status(status));
+
+    Panicked(reason).into()
+}
+
+#[cfg(unix)]
+fn describe_status(status: ExitStatus) -> String {
+    use std::os::unix::process::ExitStatusExt;
+
+    match status.signal() {
+        Some(libc::SIGXCPU) => "CPU limit exceeded".to_owned(),
+        Some(libc::SIGKILL) => "killed".to_owned(),
+        Some(sig) => format!("killed by signal {}", sig),
+        None => status.to_string(),
+    }
+}
+
+#[cfg(not(unix))]
+fn describe_status(status: ExitStatus) -> String {
+    status.to_string()
+}
+
+// Entry point of the child process. Applies limits, runs the problem and
+// writes the record to stdout.
+pub fn child_main(day: usize, problem: usize, limits: Limits) -> Result<()> {
+    std::panic::set_hook(Box::new(|info| {
+        let msg = info.payload_as_str().unwrap_or("unknown panic");
+        let msg = match info.location() {
+            Some(loc) => format!("{} at {}", msg, loc),
+            None => msg.to_owned(),
+        };
+        write_record(&format!("panic\n{}", msg));
+    }));
+
+    apply_limits(limits)?;
+
+    match crate::run_problem(day, problem, None) {
+        Ok(solved) => {
+            let alloc = solved
+                .alloc
+                .map(|a| format!(" {} {} {}", a.allocations, a.bytes, a.peak_bytes))
+                .unwrap_or_default();
+            write_record(&format!(
+                "ok {}{}\n{}",
+                solved.duration.as_nanos(),
+                alloc,
+                solved.answer
+            ));
+        }
+        Err(e) => write_record(&format!("error\n{:#}", e)),
+    }
+
+    Ok(())
+}
+
+fn write_record(record: &str) {
+    let mut stdout = std::io::stdout().lock();
+    let _ = stdout.write_all(record.as_bytes());
+    let _ = stdout.flush();
+}
+
+#[cfg(unix)]
+fn apply_limits(limits: Limits) -> Result<()> {
+    let set = |resource, value: u64| {
+        let limit = libc::rlimit {
+            rlim_cur: value as libc::rlim_t,
+            rlim_max: value as libc::rlim_t,
+        };
+        // SAFETY: setrlimit only reads the struct passed to it.
+        if unsafe { libc::setrlimit(resource, &limit) } != 0 {
+            return Err(std::io::Error::last_os_error()).context("setrlimit failed");
+        }
+        Ok(())
+    };
+
+    if let Some(mib) = limits.mem_mib {
+        set(libc::RLIMIT_AS, mib * 1024 * 1024)?;
+    }
+    if let Some(secs) = limits.cpu_secs {
+        set(libc::RLIMIT_CPU, secs)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn apply_limits(limits: Limits) -> Result<()> {
+    if limits.mem_mib.is_some() || limits.cpu_secs.is_some() {
+        anyhow::bail!("resource limits are only supported on unix");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_record_test() {
+        let solved = parse_record("ok 1500\n42").unwrap().unwrap();
+        assert_eq!(solved.answer, "42");
+        assert_eq!(solved.duration, Duration::from_nanos(1500));
+        assert_eq!(solved.alloc, None);
+
+        let solved = parse_record("ok 1500 3 200 100\n42").unwrap().unwrap();
+        assert_eq!(
+            solved.alloc,
+            Some(AllocStats {
+                allocations: 3,
+                bytes: 200,
+                peak_bytes: 100
+            })
+        );
+
+        let err = parse_record("error\nfailed to parse input")
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(err.to_string(), "failed to parse input");
+
+        let err = parse_record("panic\nboom at src/x.rs:1:1")
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<Panicked>(),
+            Some(&Panicked("boom at src/x.rs:1:1".to_owned()))
+        );
+
+        assert!(parse_record("").is_none());
+        assert!(parse_record("garbage").is_none());
+    }
+
+    #[cfg(unix)]
+    fn sh(script: &str) -> Command {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(script);
+        cmd
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_command_test() {
+        let solved = run_command(sh("printf 'ok 10\\n7'"), None).unwrap();
+        assert_eq!(solved.answer, "7");
+
+        // A panic record followed by an abort, as with panic = 'abort'.
+        let err = run_command(sh("printf 'panic\\nboom'; kill -ABRT $$"), None).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<Panicked>(),
+            Some(&Panicked("boom".to_owned()))
+        );
+
+        // Killed without writing a record.
+        let err = run_command(sh("echo 'out of memory' >&2; kill -KILL $$"), None).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<Panicked>(),
+            Some(&Panicked("out of memory".to_owned()))
+        );
+
+        let err = run_command(sh("sleep 10"), Some(Duration::from_millis(50))).unwrap_err();
+        assert!(err.is::<TimedOut>());
+    }
+}
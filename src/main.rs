@@ -1,6 +1,12 @@
+mod alloc_stats;
+mod aoc;
+mod cancel;
 mod grid;
+mod isolate;
+mod parallel;
 mod parser;
 mod range;
+mod search;
 mod solutions;
 mod utils;
 
@@ -9,13 +15,19 @@ extern crate lazy_static;
 
 use std::{
     cmp::Ordering,
+    path::PathBuf,
     time::{Duration, Instant},
 };
 
 use anyhow::{Context, Result, anyhow};
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 use rayon::prelude::*;
 
+use grid::image::{ImageFormat, Recorder};
+
+// Time each frame of a --animate GIF is shown, in hundredths of a second.
+const GIF_FRAME_DELAY: u16 = 10;
+
 #[derive(Parser)]
 #[command(name = "aoc2023")]
 #[command(author = "Stephen Weinberg")]
@@ -23,6 +35,13 @@ use rayon::prelude::*;
 struct Cli {
     #[command(subcommand)]
     commands: Commands,
+    /// Size of the thread pool used by parallel solutions and run-all
+    /// --parallel. Defaults to the number of CPUs.
+    #[arg(long, global = true)]
+    threads: Option<usize>,
+    /// Runs the loops inside each solution on a single thread
+    #[arg(long, global = true)]
+    sequential: bool,
 }
 
 #[derive(Subcommand)]
@@ -32,41 +51,211 @@ enum Commands {
         problem: usize,
         #[arg(long)]
         input: Option<String>,
+        /// Gives up on the problem after this long (e.g. "5s", "500ms")
+        #[arg(long, value_parser = humantime::parse_duration)]
+        timeout: Option<Duration>,
+        /// Draws the solution's state after the answer, for days that
+        /// support it
+        #[arg(long)]
+        render: bool,
+        #[command(flatten)]
+        animate: AnimateArgs,
     },
     RunAll {
         #[arg(long)]
         parallel: bool,
+        /// Gives up on each problem after this long (e.g. "5s", "500ms")
+        #[arg(long, value_parser = humantime::parse_duration)]
+        timeout: Option<Duration>,
+        /// Runs each problem in its own child process so a panic only
+        /// affects that problem
+        #[arg(long)]
+        isolate: bool,
+        /// Limits each child's address space to this many MiB
+        #[arg(long, requires = "isolate")]
+        mem_limit: Option<u64>,
+        /// Limits each child's CPU time to this many seconds
+        #[arg(long, requires = "isolate")]
+        cpu_limit: Option<u64>,
+    },
+    /// Downloads the puzzle input for a day into puzzle-inputs/
+    Fetch {
+        day: usize,
+        #[command(flatten)]
+        remote: RemoteArgs,
+    },
+    /// Computes the answer for a problem and submits it
+    Submit {
+        day: usize,
+        problem: usize,
+        #[command(flatten)]
+        remote: RemoteArgs,
+    },
+    /// Runs a single problem on behalf of `run-all --isolate`
+    #[command(hide = true)]
+    RunChild {
+        day: usize,
+        problem: usize,
+        #[arg(long)]
+        mem_limit: Option<u64>,
+        #[arg(long)]
+        cpu_limit: Option<u64>,
     },
 }
 
+#[derive(Args)]
+struct RemoteArgs {
+    #[arg(long, env = "AOC_BASE_URL", default_value = aoc::DEFAULT_BASE_URL)]
+    base_url: String,
+    /// Session cookie. Defaults to the contents of .aoc/session.
+    #[arg(long, env = "AOC_SESSION", hide_env_values = true)]
+    session: Option<String>,
+}
+
+#[derive(Args)]
+struct AnimateArgs {
+    /// Writes frames of the solution's simulation to this path: an animated
+    /// GIF if it ends in .gif, otherwise a directory of numbered PNGs
+    #[arg(long, value_name = "PATH")]
+    animate: Option<PathBuf>,
+    /// Keeps only every nth frame
+    #[arg(long, requires = "animate", default_value_t = 1)]
+    every: usize,
+    /// Draws each grid cell as a square of this many pixels
+    #[arg(
+        long,
+        requires = "animate",
+        default_value_t = 4,
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    scale: usize,
+}
+
+impl RemoteArgs {
+    fn client(self) -> Result<aoc::Client> {
+        let config = aoc::Config::new(self.base_url, self.session)?;
+        Ok(aoc::Client::new(config))
+    }
+}
+
 fn main() -> Result<()> {
     let cli = Cli::parse();
+    parallel::configure(cli.threads, !cli.sequential)?;
 
     match cli.commands {
         Commands::Run {
             day,
             problem,
             input,
-        } => run(day, problem, input),
-        Commands::RunAll { parallel } => run_all(parallel),
+            timeout,
+            render,
+            animate,
+        } => run(day, problem, input, timeout, render, animate),
+        Commands::RunAll {
+            parallel,
+            timeout,
+            isolate,
+            mem_limit,
+            cpu_limit,
+        } => {
+            let isolation = isolate.then_some(isolate::Limits {
+                mem_mib: mem_limit,
+                cpu_secs: cpu_limit,
+            });
+            run_all(parallel, timeout, isolation)
+        }
+        Commands::Fetch { day, remote } => fetch(day, remote),
+        Commands::Submit {
+            day,
+            problem,
+            remote,
+        } => submit(day, problem, remote),
+        Commands::RunChild {
+            day,
+            problem,
+            mem_limit,
+            cpu_limit,
+        } => isolate::child_main(
+            day,
+            problem,
+            isolate::Limits {
+                mem_mib: mem_limit,
+                cpu_secs: cpu_limit,
+            },
+        ),
     }
 }
 
-fn run(day: usize, problem: usize, input: Option<String>) -> Result<()> {
+fn run(
+    day: usize,
+    problem: usize,
+    input: Option<String>,
+    timeout: Option<Duration>,
+    render: bool,
+    animate: AnimateArgs,
+) -> Result<()> {
     let flag_input = input
         .as_ref()
         .map(|x| std::fs::read_to_string(x).context("failed to read input file"))
         .transpose()?;
 
-    let (ans, duration) = run_problem(day, problem, flag_input.as_deref())?;
+    let render_fn = render
+        .then(|| {
+            solutions::RENDERERS
+                .get(&day)
+                .ok_or(anyhow!("day {} has no renderer", day))
+        })
+        .transpose()?;
+    let animate_fn = animate
+        .animate
+        .is_some()
+        .then(|| {
+            solutions::ANIMATORS
+                .get(&day)
+                .ok_or(anyhow!("day {} has no animation", day))
+        })
+        .transpose()?;
+
+    let solved = run_problem_with_timeout(day, problem, flag_input.clone(), timeout)?;
 
-    println!("{}", ans);
-    println!("\nComputed in {:?}", duration);
+    println!("{}", solved.answer);
+    println!("\nComputed in {:?}", solved.duration);
+    if let Some(alloc) = solved.alloc {
+        println!("Memory: {}", alloc);
+    }
+
+    let input = flag_input
+        .as_deref()
+        .unwrap_or(solutions::SOLUTIONS[&day].input);
+    if let Some(render_fn) = render_fn {
+        println!("\n{}", render_fn(input, problem)?);
+    }
+
+    if let (Some(animate_fn), Some(path)) = (animate_fn, &animate.animate) {
+        let mut rec = Recorder::new(animate.every);
+        animate_fn(input, problem, &mut rec)?;
+        if path.extension().is_some_and(|x| x == "gif") {
+            rec.save_gif(path, animate.scale, GIF_FRAME_DELAY)?;
+        } else {
+            rec.save_dir(path, animate.scale, ImageFormat::Png)?;
+        }
+        println!(
+            "\nWrote {} frames to {}",
+            rec.frames().len(),
+            path.display()
+        );
+    }
 
     Ok(())
 }
 
-fn run_all(parallel: bool) -> Result<()> {
+// If isolation is set, every problem runs in a child process with the given
+// limits.
+fn run_all(
+    parallel: bool,
+    timeout: Option<Duration>,
+    isolation: Option<isolate::Limits>,
+) -> Result<()> {
     let days = {
         let mut d: Vec<usize> = solutions::SOLUTIONS.keys().copied().collect();
         d.sort_unstable();
@@ -77,37 +266,137 @@ fn run_all(parallel: bool) -> Result<()> {
         days.par_iter()
             .copied()
             .flat_map(|day| [(day, 1), (day, 2)])
-            .map(|(day, problem)| (day, problem, run_problem(day, problem, None)))
+            .map(|(day, problem)| {
+                let res = match isolation {
+                    Some(limits) => isolate::run_problem(day, problem, timeout, limits),
+                    None => run_problem_with_timeout(day, problem, None, timeout),
+                };
+                (day, problem, res)
+            })
             .collect()
     } else {
         days.iter()
             .copied()
             .flat_map(|day| [(day, 1), (day, 2)])
-            .map(|(day, problem)| (day, problem, run_problem(day, problem, None)))
+            .map(|(day, problem)| {
+                let res = match isolation {
+                    Some(limits) => isolate::run_problem(day, problem, timeout, limits),
+                    None => run_problem_with_timeout(day, problem, None, timeout),
+                };
+                (day, problem, res)
+            })
             .collect()
     };
 
     // Sort by duration in descending order. Errors are sorted at the bottom
     // by day/part.
     times.sort_by(|a, b| match (&a.2, &b.2) {
-        (Ok(a_res), Ok(b_res)) => a_res.1.cmp(&b_res.1).reverse(),
+        (Ok(a_res), Ok(b_res)) => a_res.duration.cmp(&b_res.duration).reverse(),
         (Err(_), Err(_)) => a.0.cmp(&b.0).then_with(|| a.1.cmp(&b.1)),
         (Ok(_), Err(_)) => Ordering::Less,
         (Err(_), Ok(_)) => Ordering::Greater,
     });
 
     for (day, problem, res) in &times {
-        if let Ok((_, duration)) = res {
-            println!("{:2}-{}: {:?}", day, problem, duration);
-        } else {
-            println!("{:2}-{}: ERROR", day, problem);
+        match res {
+            Ok(Solved {
+                duration,
+                alloc: Some(alloc),
+                ..
+            }) => println!(
+                "{:2}-{}: {:<14}{:>10} allocs {:>10} total {:>10} peak",
+                day,
+                problem,
+                format!("{:?}", duration),
+                alloc.allocations,
+                alloc_stats::Bytes(alloc.bytes),
+                alloc_stats::Bytes(alloc.peak_bytes),
+            ),
+            Ok(solved) => println!("{:2}-{}: {:?}", day, problem, solved.duration),
+            Err(e) if e.is::<cancel::TimedOut>() => println!("{:2}-{}: TIMEOUT", day, problem),
+            Err(e) if e.is::<isolate::Panicked>() => {
+                let msg = e.downcast_ref::<isolate::Panicked>().unwrap();
+                println!(
+                    "{:2}-{}: PANIC {}",
+                    day,
+                    problem,
+                    msg.0.lines().next().unwrap_or("")
+                );
+            }
+            Err(_) => println!("{:2}-{}: ERROR", day, problem),
         }
     }
 
     Ok(())
 }
 
-fn run_problem(day: usize, problem: usize, input: Option<&str>) -> Result<(String, Duration)> {
+fn fetch(day: usize, remote: RemoteArgs) -> Result<()> {
+    if !(1..=25).contains(&day) {
+        return Err(anyhow!("unknown day: {}", day));
+    }
+
+    match remote.client()?.fetch_input(day)? {
+        aoc::Fetched::Cached(path) => println!("{} is already downloaded", path.display()),
+        aoc::Fetched::Downloaded(path) => println!("Saved {}", path.display()),
+    }
+
+    Ok(())
+}
+
+fn submit(day: usize, problem: usize, remote: RemoteArgs) -> Result<()> {
+    let ans = run_problem(day, problem, None)?.answer;
+    println!("Answer: {}", ans);
+
+    let client = remote.client()?;
+    let mut history = client.history()?;
+
+    match history.check(day, problem, &ans)? {
+        aoc::Check::AlreadyCorrect => {
+            println!("Already accepted as the right answer");
+            return Ok(());
+        }
+        aoc::Check::Submit(warnings) => {
+            for w in warnings {
+                println!("Warning: {}", w);
+            }
+        }
+    }
+
+    let outcome = client.submit_answer(day, problem, &ans)?;
+    history.record(aoc::Attempt {
+        day,
+        part: problem,
+        answer: ans,
+        outcome,
+    })?;
+
+    println!("{}", outcome);
+    Ok(())
+}
+
+fn run_problem_with_timeout(
+    day: usize,
+    problem: usize,
+    input: Option<String>,
+    timeout: Option<Duration>,
+) -> Result<Solved> {
+    match timeout {
+        Some(timeout) => {
+            cancel::run_with_timeout(timeout, move || run_problem(day, problem, input.as_deref()))
+        }
+        None => run_problem(day, problem, input.as_deref()),
+    }
+}
+
+#[derive(Debug)]
+struct Solved {
+    answer: String,
+    duration: Duration,
+    // Only set when built with the alloc-stats feature.
+    alloc: Option<alloc_stats::AllocStats>,
+}
+
+fn run_problem(day: usize, problem: usize, input: Option<&str>) -> Result<Solved> {
     let solution = solutions::SOLUTIONS
         .get(&day)
         .ok_or(anyhow!("unknown day: {}", day))?;
@@ -120,8 +409,12 @@ fn run_problem(day: usize, problem: usize, input: Option<&str>) -> Result<(Strin
     let input = input.unwrap_or(solution.input);
 
     let start = Instant::now();
-    let ans = problem_fn(input).context("problemfn failed")?;
+    let (ans, alloc) = alloc_stats::measure(|| problem_fn(input));
     let end = Instant::now();
 
-    return Ok((ans, end.duration_since(start)));
+    Ok(Solved {
+        answer: ans.context("problemfn failed")?,
+        duration: end.duration_since(start),
+        alloc,
+    })
 }
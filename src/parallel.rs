@@ -0,0 +1,126 @@
+// The crate-wide policy for parallel loops inside a solution.
+//
+// Solutions use rayon for their hot loops and call with_policy on the
+// parallel iterator. When parallelism is turned off the iterator is not
+// split, so it runs on a single thread and gives the same answer as the
+// parallel run. The thread count applies to rayon's global pool, which
+// run-all --parallel also uses.
+//
+// Closures inside a parallel iterator run on pool threads, which don't see
+// the calling thread's cancel token or allocation measurement. They go
+// through a Caller captured before the loop instead.
+
+use std::sync::{
+    OnceLock,
+    atomic::{AtomicBool, Ordering},
+};
+
+use anyhow::{Context, Result};
+use rayon::iter::{IndexedParallelIterator, MinLen};
+
+use crate::alloc_stats;
+use crate::cancel::{self, CancelToken, Cancelled};
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+static THREADS: OnceLock<usize> = OnceLock::new();
+
+// Sets up the policy. Must be called before rayon is first used, since the
+// global pool's size can't change once it exists.
+pub fn configure(threads: Option<usize>, enabled: bool) -> Result<()> {
+    ENABLED.store(enabled, Ordering::Relaxed);
+    if let Some(n) = threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(n)
+            .build_global()
+            .context("failed to configure the thread pool")?;
+        let _ = THREADS.set(n);
+    }
+    Ok(())
+}
+
+// True if solutions should run their loops in parallel.
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+// The thread count set by configure, if any.
+pub fn threads() -> Option<usize> {
+    THREADS.get().copied()
+}
+
+// The minimum length of a piece of a parallel iterator. The whole iterator
+// is one piece if parallelism is turned off.
+fn min_len(enabled: bool) -> usize {
+    if enabled { 1 } else { usize::MAX }
+}
+
+pub trait WithPolicy: IndexedParallelIterator {
+    fn with_policy(self) -> MinLen<Self> {
+        self.with_min_len(min_len(enabled()))
+    }
+}
+
+impl<I: IndexedParallelIterator> WithPolicy for I {}
+
+// The thread-local state of the thread that starts a parallel loop.
+#[derive(Clone, Debug)]
+pub struct Caller {
+    token: CancelToken,
+    alloc: alloc_stats::Scope,
+}
+
+impl Caller {
+    pub fn capture() -> Self {
+        Self {
+            token: cancel::current().unwrap_or_default(),
+            alloc: alloc_stats::Scope::current(),
+        }
+    }
+
+    // Fails once the caller is cancelled.
+    pub fn check(&self) -> Result<(), Cancelled> {
+        if self.token.is_cancelled() {
+            return Err(Cancelled);
+        }
+        Ok(())
+    }
+
+    // Runs f with its allocations counted towards the caller's measurement.
+    pub fn enter<R>(&self, f: impl FnOnce() -> R) -> R {
+        self.alloc.enter(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rayon::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn min_len_test() {
+        let sum = |v: &[u64], enabled| {
+            v.par_iter()
+                .with_min_len(min_len(enabled))
+                .map(|x| x * x)
+                .sum::<u64>()
+        };
+        let v: Vec<u64> = (0..10_000).collect();
+        let expected = v.iter().map(|x| x * x).sum::<u64>();
+
+        assert_eq!(sum(&v, true), expected);
+        assert_eq!(sum(&v, false), expected);
+    }
+
+    #[test]
+    fn caller_test() {
+        let token = CancelToken::new();
+        let caller = cancel::with_token(token.clone(), Caller::capture);
+        let check = || (0..100).into_par_iter().try_for_each(|_| caller.check());
+
+        assert_eq!(check(), Ok(()));
+        token.cancel();
+        assert_eq!(check(), Err(Cancelled));
+        assert_eq!(Caller::capture().check(), Ok(()));
+    }
+}
@@ -1,31 +1,68 @@
 use std::str::FromStr;
 
 use nom::combinator::all_consuming;
+use nom::error::{ErrorKind, FromExternalError, ParseError};
 use prelude::*;
 
+use crate::grid::{FromGridChar, Grid, GridError, Markers};
+
 pub mod prelude {
+    // Not every combinator is used by a solution at any given time.
+    #[allow(unused_imports)]
     pub use nom::{
-        AsChar, IResult, Input, Mode, Parser,
+        AsChar, Input, Mode, Parser,
         branch::alt,
         bytes::{is_a, tag, take},
-        character::complete::{char, digit1, line_ending, multispace0, one_of, space0, space1},
+        character::complete::{
+            anychar, char, digit1, line_ending, multispace0, one_of, space0, space1,
+        },
         combinator::{eof, opt, recognize, value, verify},
         multi::{fold_many1, many1, separated_list1},
         sequence::{delimited, separated_pair, terminated},
     };
 
     #[allow(unused_imports)]
-    pub use super::{complete, int, uint, ws_all_consuming, ws_line};
+    pub use super::{
+        Error, IResult, complete, grid, grid_of, int, uint, ws_all_consuming, ws_line,
+    };
 }
 
-pub fn uint<'a, T: FromStr>() -> impl Parser<&'a str, Output = T, Error = nom::error::Error<&'a str>>
-{
+// nom's default error, plus the reason a grid was rejected.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Error<I> {
+    pub input: I,
+    pub code: ErrorKind,
+    pub grid: Option<GridError>,
+}
+
+impl<I> ParseError<I> for Error<I> {
+    fn from_error_kind(input: I, code: ErrorKind) -> Self {
+        Self {
+            input,
+            code,
+            grid: None,
+        }
+    }
+
+    fn append(_: I, _: ErrorKind, other: Self) -> Self {
+        other
+    }
+}
+
+impl<I, E> FromExternalError<I, E> for Error<I> {
+    fn from_external_error(input: I, code: ErrorKind, _: E) -> Self {
+        Self::from_error_kind(input, code)
+    }
+}
+
+pub type IResult<I, O> = nom::IResult<I, O, Error<I>>;
+
+pub fn uint<'a, T: FromStr>() -> impl Parser<&'a str, Output = T, Error = Error<&'a str>> {
     digit1.map_res(|x: &str| x.parse())
 }
 
 #[allow(dead_code)]
-pub fn int<'a, T: FromStr>() -> impl Parser<&'a str, Output = T, Error = nom::error::Error<&'a str>>
-{
+pub fn int<'a, T: FromStr>() -> impl Parser<&'a str, Output = T, Error = Error<&'a str>> {
     let num = (opt(tag("-")), digit1);
     recognize(num).map_res(|x: &str| x.parse())
 }
@@ -58,3 +95,142 @@ where
     let end_of_line = alt((line_ending, eof));
     delimited(space0, parser, (space0, end_of_line))
 }
+
+// Parses one grid row per line using elem for each cell. A row whose length
+// differs from the first row is a hard failure positioned at the start of
+// that row, so the error can be traced back to its line.
+pub fn grid_of<'a, T, P>(elem: P) -> impl Parser<&'a str, Output = Grid<T>, Error = Error<&'a str>>
+where
+    P: Parser<&'a str, Output = T, Error = Error<&'a str>>,
+{
+    let mut row = ws_line(many1(elem));
+
+    move |input: &'a str| {
+        let mut rows: Vec<Vec<T>> = Vec::new();
+        let mut rest = input;
+
+        loop {
+            match row.parse(rest) {
+                Ok((next, r)) => {
+                    if let Some(first) = rows.first().filter(|first| first.len() != r.len()) {
+                        return Err(nom::Err::Failure(Error {
+                            input: rest,
+                            code: ErrorKind::Verify,
+                            grid: Some(GridError::Ragged {
+                                row: rows.len(),
+                                expected: first.len(),
+                                actual: r.len(),
+                            }),
+                        }));
+                    }
+                    rows.push(r);
+                    rest = next;
+                }
+                Err(nom::Err::Error(_)) if !rows.is_empty() => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        // Rows are non-empty and equal length, so this cannot fail.
+        let grid = Grid::try_new(rows).map_err(|e| {
+            nom::Err::Failure(Error {
+                input,
+                code: ErrorKind::Verify,
+                grid: Some(e),
+            })
+        })?;
+        Ok((rest, grid))
+    }
+}
+
+// Parses a grid of T cells, one character per cell, and records the
+// positions of its marker cells.
+pub fn grid<'a, T: FromGridChar + Clone>()
+-> impl Parser<&'a str, Output = (Grid<T>, Markers<T>), Error = Error<&'a str>> {
+    grid_of(anychar.map_opt(T::from_char)).map(|grid: Grid<T>| {
+        let markers = grid
+            .iter_items()
+            .filter(|(_, c)| c.is_marker())
+            .map(|(p, c)| (p, c.clone()))
+            .collect();
+        (grid, Markers(markers))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::Point;
+
+    #[test]
+    fn grid_of_test() {
+        let mut parser = ws_all_consuming(grid_of(one_of("ab")));
+
+        let (_, grid) = parser.parse_complete("ab\nba\n").unwrap();
+        assert_eq!(grid.size(), (2, 2));
+
+        let Err(nom::Err::Failure(e)) = parser.parse_complete("ab\nb\nab") else {
+            panic!("expected failure");
+        };
+        assert_eq!(e.input, "b\nab");
+        assert_eq!(
+            e.grid,
+            Some(GridError::Ragged {
+                row: 1,
+                expected: 2,
+                actual: 1
+            })
+        );
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    enum Cell {
+        Open,
+        Start,
+    }
+
+    impl FromGridChar for Cell {
+        fn from_char(c: char) -> Option<Self> {
+            match c {
+                '.' => Some(Cell::Open),
+                'S' => Some(Cell::Start),
+                _ => None,
+            }
+        }
+
+        fn to_char(&self) -> char {
+            match self {
+                Cell::Open => '.',
+                Cell::Start => 'S',
+            }
+        }
+
+        fn is_marker(&self) -> bool {
+            *self == Cell::Start
+        }
+    }
+
+    #[test]
+    fn grid_test() {
+        let input = "...\n.S.\n..S\n";
+        let (_, (g, markers)) = ws_all_consuming(grid::<Cell>())
+            .parse_complete(input)
+            .unwrap();
+        assert_eq!(g.size(), (3, 3));
+        assert_eq!(markers.find(&Cell::Start), Some(Point::new(1, 1)));
+        assert_eq!(markers.iter().count(), 2);
+        assert_eq!(g.to_string(), input);
+
+        let (_, (g, markers)) = ws_all_consuming(grid::<u8>())
+            .parse_complete("12\n90")
+            .unwrap();
+        assert_eq!(g.cells(), &[1, 2, 9, 0]);
+        assert!(markers.iter().next().is_none());
+
+        assert!(
+            ws_all_consuming(grid::<Cell>())
+                .parse_complete("..\n.x")
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,605 @@
+#![allow(dead_code)]
+
+// Generic shortest path searches over arbitrary state types.
+//
+// States are anything Hash + Eq + Clone, e.g. a (Point, Direction) pair.
+// Callers provide a successor closure and a goal predicate. The search stops
+// once every goal state at the optimal distance has been reached, and keeps
+// every optimal predecessor of each state it settles so that all shortest
+// paths can be recovered, not just one.
+//
+// Edge costs may be zero. A start state can then have predecessors, but it
+// never has a parent, so path reconstruction always terminates.
+//
+// When only the distance is needed, dijkstra_distance skips the predecessor
+// bookkeeping and just tracks which states have been settled.
+
+use std::{
+    collections::{BinaryHeap, VecDeque},
+    hash::Hash,
+    ops::Deref,
+};
+
+use ahash::{AHashMap, AHashSet};
+
+use crate::grid::{Direction, Point, bits::DirectionBitGrid};
+use crate::utils::RevHeapElem;
+
+pub struct Node<S> {
+    dist: u64,
+    // The predecessors on shortest paths to this state. The first one is
+    // the parent used for path reconstruction and is kept inline, since most
+    // states only have one.
+    first: Option<S>,
+    rest: Vec<S>,
+    settled: bool,
+    start: bool,
+}
+
+// Where a search keeps the bookkeeping for each state it reaches.
+// AHashMap works for any state type. DenseMap avoids hashing for states that
+// can be numbered by a small index, e.g. grid positions.
+pub trait NodeMap<S> {
+    fn get(&self, s: &S) -> Option<&Node<S>>;
+    fn get_mut(&mut self, s: &S) -> Option<&mut Node<S>>;
+    fn insert(&mut self, s: S, node: Node<S>);
+    fn iter<'a>(&'a self) -> impl Iterator<Item = (&'a S, &'a Node<S>)>
+    where
+        S: 'a;
+}
+
+impl<S: Hash + Eq> NodeMap<S> for AHashMap<S, Node<S>> {
+    fn get(&self, s: &S) -> Option<&Node<S>> {
+        AHashMap::get(self, s)
+    }
+
+    fn get_mut(&mut self, s: &S) -> Option<&mut Node<S>> {
+        AHashMap::get_mut(self, s)
+    }
+
+    fn insert(&mut self, s: S, node: Node<S>) {
+        AHashMap::insert(self, s, node);
+    }
+
+    fn iter<'a>(&'a self) -> impl Iterator<Item = (&'a S, &'a Node<S>)>
+    where
+        S: 'a,
+    {
+        self.deref().iter()
+    }
+}
+
+// A NodeMap for states that can be numbered densely. index must map every
+// reachable state to a distinct value below size. Only the index table is
+// sized up front; nodes are stored in the order they are reached.
+pub struct DenseMap<S, F> {
+    index: F,
+    // 0 if the state hasn't been reached, otherwise its position in nodes
+    // plus one.
+    slots: Vec<u32>,
+    nodes: Vec<(S, Node<S>)>,
+}
+
+impl<S, F: Fn(&S) -> usize> DenseMap<S, F> {
+    pub fn new(size: usize, index: F) -> Self {
+        Self {
+            index,
+            slots: vec![0; size],
+            nodes: Vec::new(),
+        }
+    }
+
+    fn slot(&self, s: &S) -> Option<usize> {
+        (self.slots[(self.index)(s)] as usize).checked_sub(1)
+    }
+}
+
+impl<S, F: Fn(&S) -> usize> NodeMap<S> for DenseMap<S, F> {
+    fn get(&self, s: &S) -> Option<&Node<S>> {
+        self.slot(s).map(|i| &self.nodes[i].1)
+    }
+
+    fn get_mut(&mut self, s: &S) -> Option<&mut Node<S>> {
+        self.slot(s).map(|i| &mut self.nodes[i].1)
+    }
+
+    fn insert(&mut self, s: S, node: Node<S>) {
+        match self.slot(&s) {
+            Some(i) => self.nodes[i] = (s, node),
+            None => {
+                let i = (self.index)(&s);
+                self.nodes.push((s, node));
+                self.slots[i] = self.nodes.len() as u32;
+            }
+        }
+    }
+
+    fn iter<'a>(&'a self) -> impl Iterator<Item = (&'a S, &'a Node<S>)>
+    where
+        S: 'a,
+    {
+        self.nodes.iter().map(|(s, n)| (s, n))
+    }
+}
+
+// The set of settled states for dijkstra_distance. DirectionBitGrid suits
+// (Point, Direction) states on a grid.
+pub trait Visited<S> {
+    // Marks s as visited. Returns false if it already was.
+    fn visit(&mut self, s: &S) -> bool;
+    fn is_visited(&self, s: &S) -> bool;
+}
+
+impl<S: Hash + Eq + Clone> Visited<S> for AHashSet<S> {
+    fn visit(&mut self, s: &S) -> bool {
+        self.insert(s.clone())
+    }
+
+    fn is_visited(&self, s: &S) -> bool {
+        self.contains(s)
+    }
+}
+
+impl Visited<(Point, Direction)> for DirectionBitGrid {
+    fn visit(&mut self, &(p, d): &(Point, Direction)) -> bool {
+        self.insert(p, d)
+    }
+
+    fn is_visited(&self, &(p, d): &(Point, Direction)) -> bool {
+        self.contains(p, d)
+    }
+}
+
+pub struct SearchResult<S, M = AHashMap<S, Node<S>>> {
+    nodes: M,
+    goals: Vec<S>,
+}
+
+impl<S: Hash + Eq + Clone, M: NodeMap<S>> SearchResult<S, M> {
+    fn new(nodes: M) -> Self {
+        Self {
+            nodes,
+            goals: Vec::new(),
+        }
+    }
+
+    fn add_start(&mut self, s: S) {
+        self.nodes.insert(
+            s,
+            Node {
+                dist: 0,
+                first: None,
+                rest: Vec::new(),
+                settled: false,
+                start: true,
+            },
+        );
+    }
+
+    // Records an edge from settled state from to to. Returns true if it
+    // improved the distance to to, which then needs to be (re)queued.
+    //
+    // An edge that ties the distance of an already settled state is still a
+    // shortest path to it. That happens when A* breaks an f tie in favour of
+    // to, or with zero-cost edges.
+    fn relax(&mut self, from: &S, to: S, dist: u64) -> bool {
+        match self.nodes.get_mut(&to) {
+            Some(n) if dist > n.dist || (n.settled && dist != n.dist) => false,
+            Some(n) if dist == n.dist => {
+                if n.first.is_none() {
+                    n.first = Some(from.clone());
+                } else {
+                    n.rest.push(from.clone());
+                }
+                false
+            }
+            _ => {
+                self.nodes.insert(
+                    to,
+                    Node {
+                        dist,
+                        first: Some(from.clone()),
+                        rest: Vec::new(),
+                        settled: false,
+                        start: false,
+                    },
+                );
+                true
+            }
+        }
+    }
+
+    // Marks s as settled if dist is its final distance. Returns false for
+    // stale queue entries.
+    fn settle(&mut self, s: &S, dist: u64) -> bool {
+        let n = self.nodes.get_mut(s).unwrap();
+        if n.settled || n.dist != dist {
+            return false;
+        }
+        n.settled = true;
+        true
+    }
+
+    // The goal states reached at the optimal distance, in the order they
+    // were found. Empty if no goal is reachable.
+    pub fn goals(&self) -> &[S] {
+        &self.goals
+    }
+
+    pub fn goal_distance(&self) -> Option<u64> {
+        self.goals.first().and_then(|g| self.distance(g))
+    }
+
+    // The shortest distance to s if the search reached it. Distances of
+    // states that were not settled before the search stopped may not be
+    // optimal.
+    pub fn distance(&self, s: &S) -> Option<u64> {
+        self.nodes.get(s).map(|n| n.dist)
+    }
+
+    pub fn distances(&self) -> impl Iterator<Item = (&S, u64)> {
+        self.nodes.iter().map(|(s, n)| (s, n.dist))
+    }
+
+    // The predecessor s was first reached from at its final distance. None
+    // for start states.
+    pub fn parent(&self, s: &S) -> Option<&S> {
+        self.nodes
+            .get(s)
+            .filter(|n| !n.start)
+            .and_then(|n| n.first.as_ref())
+    }
+
+    // Every state that precedes s on some shortest path.
+    pub fn predecessors(&self, s: &S) -> impl Iterator<Item = &S> {
+        self.nodes
+            .get(s)
+            .into_iter()
+            .flat_map(|n| n.first.iter().chain(&n.rest))
+    }
+
+    // A shortest path from a start state to s, inclusive.
+    pub fn path(&self, s: &S) -> Option<Vec<S>> {
+        self.nodes.get(s)?;
+        let mut ret = vec![s.clone()];
+        while let Some(p) = self.parent(ret.last().unwrap()) {
+            ret.push(p.clone());
+        }
+        ret.reverse();
+        Some(ret)
+    }
+
+    pub fn goal_path(&self) -> Option<Vec<S>> {
+        self.path(self.goals.first()?)
+    }
+
+    // Every state on any shortest path to any of targets.
+    pub fn optimal_states<'a>(&'a self, targets: impl IntoIterator<Item = &'a S>) -> AHashSet<S> {
+        let mut ret = AHashSet::new();
+        let mut stack: Vec<&S> = targets
+            .into_iter()
+            .filter(|s| self.nodes.get(s).is_some())
+            .collect();
+        while let Some(s) = stack.pop() {
+            if ret.insert(s.clone()) {
+                stack.extend(self.predecessors(s));
+            }
+        }
+        ret
+    }
+}
+
+// Breadth first search where every edge costs 1.
+pub fn bfs<S, I>(
+    starts: impl IntoIterator<Item = S>,
+    mut successors: impl FnMut(&S) -> I,
+    mut is_goal: impl FnMut(&S) -> bool,
+) -> SearchResult<S>
+where
+    S: Hash + Eq + Clone,
+    I: IntoIterator<Item = S>,
+{
+    let mut ret = SearchResult::new(AHashMap::new());
+    let mut queue = VecDeque::new();
+    for s in starts {
+        ret.add_start(s.clone());
+        queue.push_back((s, 0));
+    }
+
+    while let Some((cur, dist)) = queue.pop_front() {
+        if ret.goal_distance().is_some_and(|d| dist > d) {
+            break;
+        }
+        if !ret.settle(&cur, dist) {
+            continue;
+        }
+        if is_goal(&cur) {
+            ret.goals.push(cur.clone());
+        }
+
+        for next in successors(&cur) {
+            if ret.relax(&cur, next.clone(), dist + 1) {
+                queue.push_back((next, dist + 1));
+            }
+        }
+    }
+
+    ret
+}
+
+// Dijkstra's algorithm. successors returns each next state with the cost of
+// the edge to it.
+pub fn dijkstra<S, I>(
+    starts: impl IntoIterator<Item = S>,
+    successors: impl FnMut(&S) -> I,
+    is_goal: impl FnMut(&S) -> bool,
+) -> SearchResult<S>
+where
+    S: Hash + Eq + Clone,
+    I: IntoIterator<Item = (S, u64)>,
+{
+    dijkstra_in(AHashMap::new(), starts, successors, is_goal)
+}
+
+// Like dijkstra, but keeps its bookkeeping in nodes, which must be empty.
+pub fn dijkstra_in<S, I, M>(
+    nodes: M,
+    starts: impl IntoIterator<Item = S>,
+    successors: impl FnMut(&S) -> I,
+    is_goal: impl FnMut(&S) -> bool,
+) -> SearchResult<S, M>
+where
+    S: Hash + Eq + Clone,
+    I: IntoIterator<Item = (S, u64)>,
+    M: NodeMap<S>,
+{
+    astar_in(nodes, starts, successors, |_| 0, is_goal)
+}
+
+// The shortest distance to any goal, without recording how it was reached.
+// Cheaper than dijkstra when no path is needed. visited must be empty.
+pub fn dijkstra_distance<S, I>(
+    mut visited: impl Visited<S>,
+    starts: impl IntoIterator<Item = S>,
+    mut successors: impl FnMut(&S) -> I,
+    mut is_goal: impl FnMut(&S) -> bool,
+) -> Option<u64>
+where
+    I: IntoIterator<Item = (S, u64)>,
+{
+    let mut frontier: BinaryHeap<_> = starts
+        .into_iter()
+        .map(|s| RevHeapElem { key: 0, value: s })
+        .collect();
+
+    while let Some(RevHeapElem {
+        key: dist,
+        value: cur,
+    }) = frontier.pop()
+    {
+        if !visited.visit(&cur) {
+            continue;
+        }
+        if is_goal(&cur) {
+            return Some(dist);
+        }
+
+        for (next, cost) in successors(&cur) {
+            if !visited.is_visited(&next) {
+                frontier.push(RevHeapElem {
+                    key: dist + cost,
+                    value: next,
+                });
+            }
+        }
+    }
+
+    None
+}
+
+// A* search. heuristic must never overestimate the distance to the nearest
+// goal and must be consistent, or the distances may not be optimal.
+pub fn astar<S, I>(
+    starts: impl IntoIterator<Item = S>,
+    successors: impl FnMut(&S) -> I,
+    heuristic: impl FnMut(&S) -> u64,
+    is_goal: impl FnMut(&S) -> bool,
+) -> SearchResult<S>
+where
+    S: Hash + Eq + Clone,
+    I: IntoIterator<Item = (S, u64)>,
+{
+    astar_in(AHashMap::new(), starts, successors, heuristic, is_goal)
+}
+
+// Like astar, but keeps its bookkeeping in nodes, which must be empty.
+pub fn astar_in<S, I, M>(
+    nodes: M,
+    starts: impl IntoIterator<Item = S>,
+    mut successors: impl FnMut(&S) -> I,
+    mut heuristic: impl FnMut(&S) -> u64,
+    mut is_goal: impl FnMut(&S) -> bool,
+) -> SearchResult<S, M>
+where
+    S: Hash + Eq + Clone,
+    I: IntoIterator<Item = (S, u64)>,
+    M: NodeMap<S>,
+{
+    let mut ret = SearchResult::new(nodes);
+    let mut frontier = BinaryHeap::new();
+    for s in starts {
+        ret.add_start(s.clone());
+        frontier.push(RevHeapElem {
+            key: heuristic(&s),
+            value: (s, 0),
+        });
+    }
+
+    while let Some(RevHeapElem {
+        key: estimate,
+        value: (cur, dist),
+    }) = frontier.pop()
+    {
+        if ret.goal_distance().is_some_and(|d| estimate > d) {
+            break;
+        }
+        if !ret.settle(&cur, dist) {
+            continue;
+        }
+        if is_goal(&cur) {
+            ret.goals.push(cur.clone());
+        }
+
+        for (next, cost) in successors(&cur) {
+            let next_dist = dist + cost;
+            if ret.relax(&cur, next.clone(), next_dist) {
+                frontier.push(RevHeapElem {
+                    key: next_dist + heuristic(&next),
+                    value: (next, next_dist),
+                });
+            }
+        }
+    }
+
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::{Direction, Grid, Point};
+
+    // 0 is open and 1 is a wall.
+    fn test_grid() -> Grid<u8> {
+        Grid::new(vec![
+            vec![0, 0, 0, 0],
+            vec![0, 1, 1, 0],
+            vec![0, 0, 0, 0],
+            vec![1, 1, 0, 1],
+        ])
+    }
+
+    fn open_neighbors(g: &Grid<u8>, p: Point) -> impl Iterator<Item = Point> + '_ {
+        g.neighbors(p).filter(|&n| g[n] == 0)
+    }
+
+    #[test]
+    fn bfs_test() {
+        let g = test_grid();
+        let end = Point::new(3, 2);
+        let res = bfs(
+            [Point::new(0, 0)],
+            |&p| open_neighbors(&g, p),
+            |&p| p == end,
+        );
+
+        assert_eq!(res.goals(), &[end]);
+        assert_eq!(res.goal_distance(), Some(5));
+        let path = res.goal_path().unwrap();
+        assert_eq!(path.len(), 6);
+        assert_eq!(path[0], Point::new(0, 0));
+        assert!(path.windows(2).all(|w| w[0].manhattan_distance(w[1]) == 1));
+
+        // Around the wall either way.
+        assert_eq!(res.predecessors(&end).count(), 2);
+        assert_eq!(res.optimal_states([&end]).len(), 10);
+
+        let res = bfs([Point::new(0, 0)], |&p| open_neighbors(&g, p), |_| false);
+        assert_eq!(res.distance(&Point::new(2, 3)), Some(5));
+        assert_eq!(res.distance(&Point::new(1, 1)), None);
+    }
+
+    #[test]
+    fn astar_all_predecessors_test() {
+        // With Manhattan distance on an open grid, every cell on a shortest
+        // path has the same f, so the heap order between them is arbitrary.
+        let g = Grid::from_fn(4, 4, |_| 0u8);
+        let start = Point::new(0, 0);
+        let end = Point::new(3, 3);
+        let res = astar(
+            [start],
+            |&p| open_neighbors(&g, p).map(|n| (n, 1)),
+            |p| p.manhattan_distance(end) as u64,
+            |&p| p == end,
+        );
+
+        assert_eq!(res.goal_distance(), Some(6));
+        assert_eq!(res.optimal_states([&end]).len(), 16);
+        for p in g.iter_points().filter(|&p| p != start) {
+            let mut preds: Vec<_> = res.predecessors(&p).copied().collect();
+            preds.sort_by_key(|p| (p.y, p.x));
+            let mut expected: Vec<_> = [p.next(Direction::North), p.next(Direction::West)]
+                .into_iter()
+                .flatten()
+                .collect();
+            expected.sort_by_key(|p| (p.y, p.x));
+            assert_eq!(preds, expected, "{:?}", p);
+        }
+    }
+
+    #[test]
+    fn zero_cost_test() {
+        // 0 and 1 are joined both ways for free.
+        let successors = |&s: &u8| match s {
+            0 => vec![(1, 0), (2, 1)],
+            1 => vec![(0, 0), (2, 1)],
+            _ => vec![],
+        };
+        let res = dijkstra([0u8], successors, |&s| s == 2);
+
+        assert_eq!(res.goal_distance(), Some(1));
+        let mut preds: Vec<_> = res.predecessors(&2).copied().collect();
+        preds.sort();
+        assert_eq!(preds, vec![0, 1]);
+        assert!(res.predecessors(&0).eq(&[1]));
+        assert_eq!(res.parent(&0), None);
+        assert_eq!(res.path(&1), Some(vec![0, 1]));
+        assert_eq!(res.optimal_states([&2]).len(), 3);
+    }
+
+    #[test]
+    fn dijkstra_test() {
+        // Entering a cell costs its value, so the cheap path is the long
+        // one.
+        let g = Grid::new(vec![vec![1u8, 9, 1], vec![1, 1, 1]]);
+        let start = Point::new(0, 0);
+        let end = Point::new(2, 0);
+        let successors = |&p: &Point| g.neighbors(p).map(|n| (n, g[n] as u64));
+
+        let res = dijkstra([start], successors, |&p| p == end);
+        assert_eq!(res.goal_distance(), Some(4));
+        assert_eq!(
+            res.goal_path().unwrap(),
+            vec![
+                Point::new(0, 0),
+                Point::new(0, 1),
+                Point::new(1, 1),
+                Point::new(2, 1),
+                Point::new(2, 0),
+            ]
+        );
+
+        let dense = DenseMap::new(g.width() * g.height(), |p: &Point| p.y * g.width() + p.x);
+        let res = dijkstra_in(dense, [start], successors, |&p| p == end);
+        assert_eq!(res.goal_distance(), Some(4));
+        assert_eq!(res.path(&end).map(|p| p.len()), Some(5));
+        assert_eq!(res.distances().count(), 6);
+
+        let dist = dijkstra_distance(AHashSet::new(), [start], successors, |&p| p == end);
+        assert_eq!(dist, Some(4));
+        let dist = dijkstra_distance(AHashSet::new(), [start], successors, |_| false);
+        assert_eq!(dist, None);
+
+        let manhattan = |p: &Point| p.manhattan_distance(end) as u64;
+        let res = astar([start], successors, manhattan, |&p| p == end);
+        assert_eq!(res.goal_distance(), Some(4));
+
+        // Several goal states at the same distance are all reported.
+        let res = dijkstra([Point::new(1, 1)], successors, |p| p.y == 0);
+        let goals: AHashSet<_> = res.goals().iter().copied().collect();
+        assert_eq!(
+            goals,
+            AHashSet::from_iter([Point::new(0, 0), Point::new(2, 0)])
+        );
+    }
+}
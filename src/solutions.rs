@@ -1,5 +1,7 @@
 use std::collections::HashMap;
 
+use crate::grid::image::Recorder;
+
 macro_rules! days {
     ($($x:ident), *) => {
         $(
@@ -35,6 +37,36 @@ fn mod_name_to_num(name: &str) -> usize {
 
 pub type ProblemFn = fn(&str) -> Result<String, anyhow::Error>;
 
+// Draws the state of a problem's solution for debugging. Takes the input and
+// the problem number.
+pub type RenderFn = fn(&str, usize) -> Result<String, anyhow::Error>;
+
+const RENDERERS_ARR: &[(&str, RenderFn)] = &[
+    ("day06", day06::render),
+    ("day16", day16::render),
+    ("day22", day22::render),
+    ("day23", day23::render),
+];
+
+// Records frames of a problem's simulation with `run --animate`. Takes the
+// input and the problem number.
+pub type AnimateFn = fn(&str, usize, &mut Recorder) -> Result<(), anyhow::Error>;
+
+const ANIMATORS_ARR: &[(&str, AnimateFn)] = &[("day06", day06::animate)];
+
+lazy_static! {
+    pub static ref RENDERERS: HashMap<usize, RenderFn> = HashMap::from_iter(
+        RENDERERS_ARR
+            .iter()
+            .map(|&(day, f)| (mod_name_to_num(day), f))
+    );
+    pub static ref ANIMATORS: HashMap<usize, AnimateFn> = HashMap::from_iter(
+        ANIMATORS_ARR
+            .iter()
+            .map(|&(day, f)| (mod_name_to_num(day), f))
+    );
+}
+
 pub struct Solution {
     pub day: &'static str,
     pub problem1: ProblemFn,
@@ -48,13 +80,28 @@ mod prelude {
     pub use anyhow::{Context, bail};
 
     macro_rules! parse {
-        ($input:expr) => {
-            <::std::result::Result<_, _> as ::nom::Finish<_, _, _>>::finish(
-                parser::parse($input).map_err(|x| x.to_owned()),
-            )
-            .context("failed to parse input")?
-            .1
-        };
+        ($input:expr) => {{
+            let input = $input;
+            match <::std::result::Result<_, _> as ::nom::Finish<_, _, _>>::finish(parser::parse(
+                input,
+            )) {
+                Ok((_, v)) => v,
+                Err(e) => return Err(parse_error(input, e)),
+            }
+        }};
+    }
+
+    // Converts a nom error into an error that names the line it occurred on.
+    pub fn parse_error(input: &str, e: crate::parser::Error<&str>) -> anyhow::Error {
+        let offset = input.len() - e.input.len();
+        let line = input[..offset].matches('\n').count() + 1;
+        let near = e.input.lines().next().unwrap_or("").trim();
+
+        match e.grid {
+            Some(grid) => anyhow::anyhow!("{} at line {}: {:?}", grid, line, near),
+            None => anyhow::anyhow!("{:?} error at line {}: {:?}", e.code, line, near),
+        }
+        .context("failed to parse input")
     }
 }
 
@@ -1,14 +1,17 @@
-use crate::grid::{Direction, Grid, Point};
+use crate::cancel::Cancelled;
+use crate::grid::bits::{BitGrid, DirectionBitGrid};
+use crate::grid::image::{Frame, Recorder, Rgb};
+use crate::grid::obstacles::ObstacleIndex;
+use crate::grid::{Direction, FromGridChar, Grid, Markers, Point};
+use crate::parallel::{Caller, WithPolicy};
 use crate::solutions::prelude::*;
 
-use ahash::AHashSet;
-
-use std::collections::BTreeSet;
+use rayon::prelude::*;
 
 pub fn problem1(input: &str) -> Result<String, anyhow::Error> {
-    let grid = parse!(input);
+    let (grid, markers) = parse!(input);
 
-    let Some((start, _)) = grid.iter_items().find(|(_, e)| **e == GridElem::GuardStart) else {
+    let Some(start) = markers.find(&GridElem::GuardStart) else {
         bail!("no guard start found")
     };
 
@@ -17,155 +20,323 @@ pub fn problem1(input: &str) -> Result<String, anyhow::Error> {
         bail!("loop detected");
     }
 
-    let guard_locations = AHashSet::from_iter(states.into_iter().map(|(p, _)| p));
-    Ok(guard_locations.len().to_string())
+    Ok(states.points().count().to_string())
 }
 
 pub fn problem2(input: &str) -> Result<String, anyhow::Error> {
-    let grid = parse!(input);
+    let (grid, markers) = parse!(input);
 
-    let Some((start, _)) = grid.iter_items().find(|(_, e)| **e == GridElem::GuardStart) else {
+    let Some(start) = markers.find(&GridElem::GuardStart) else {
         bail!("no guard start found")
     };
 
-    let (states, is_loop) = get_guard_states(&grid, start);
-    if is_loop {
-        bail!("unexpected loop detected");
+    let ans = loop_obstructions(&grid, start)?.len();
+    Ok(ans.to_string())
+}
+
+// Draws the guard's route for problem 1 and the obstructions that cause a
+// loop for problem 2.
+pub fn render(input: &str, problem: usize) -> Result<String, anyhow::Error> {
+    let (grid, markers) = parse!(input);
+
+    let Some(start) = markers.find(&GridElem::GuardStart) else {
+        bail!("no guard start found")
+    };
+
+    let render = match problem {
+        1 => {
+            let (states, _) = get_guard_states(&grid, start);
+            grid.render().overlay('X', states.points().iter())
+        }
+        _ => grid.render().overlay('O', loop_obstructions(&grid, start)?),
+    };
+
+    Ok(render.overlay('^', [start]).rulers(true).to_string())
+}
+
+// Animates the guard's patrol for problem 1 with one frame per turn.
+pub fn animate(input: &str, problem: usize, rec: &mut Recorder) -> Result<(), anyhow::Error> {
+    const EMPTY: Rgb = [24, 24, 32];
+    const WALL: Rgb = [160, 160, 160];
+    const VISITED: Rgb = [220, 180, 40];
+    const GUARD: Rgb = [230, 40, 40];
+
+    if problem != 1 {
+        bail!("only problem 1 can be animated");
     }
 
-    let candidates = AHashSet::from_iter(
-        states
-            .into_iter()
-            .filter(|(p, _)| *p != start)
-            .map(|(p, _)| p),
-    );
+    let (grid, markers) = parse!(input);
+
+    let Some(start) = markers.find(&GridElem::GuardStart) else {
+        bail!("no guard start found")
+    };
 
-    let mut g_idx = GridIndex::new(&grid);
+    let palette = |c: &GridElem| match c {
+        GridElem::Wall => WALL,
+        _ => EMPTY,
+    };
+    let mut frame = Frame::from_grid(&grid, &palette);
+    let mut prev: Option<(Point, Direction)> = None;
+    walk_guard(&grid, start, |pos, dir| {
+        if let Some((prev_pos, prev_dir)) = prev {
+            frame.paint(prev_pos, VISITED);
+            if prev_dir != dir {
+                rec.step(|| frame.clone());
+            }
+        }
+        frame.paint(pos, GUARD);
+        prev = Some((pos, dir));
+    });
+    rec.push(frame);
 
-    let ans = candidates
-        .iter()
-        .filter(|&&c| {
-            g_idx.insert(c);
-            let is_loop = detect_loop(&g_idx, start, Direction::Down);
-            g_idx.remove(c);
-            is_loop
-        })
-        .count();
+    Ok(())
+}
 
-    Ok(ans.to_string())
+// Returns every position where a new obstruction makes the guard loop.
+//
+// An obstruction only changes the route from the first time the guard would
+// walk into it, so each candidate is simulated from the state just before
+// that point instead of from the start.
+fn loop_obstructions(grid: &Grid<GridElem>, start: Point) -> Result<Vec<Point>, anyhow::Error> {
+    let mut first_visits = BitGrid::for_grid(grid);
+    first_visits.insert(start);
+    let mut candidates = Vec::new();
+    let (_, is_loop) = walk_guard(grid, start, |pos, dir| {
+        if first_visits.insert(pos) {
+            // The guard walked into pos, so the previous cell is in grid.
+            candidates.push((pos, pos.next(dir.opposite()).unwrap(), dir));
+        }
+    });
+    if is_loop {
+        bail!("unexpected loop detected");
+    }
+
+    let jumps = JumpTable::new(grid);
+    let caller = Caller::capture();
+    let ret: Vec<Option<Point>> = candidates
+        .par_iter()
+        .with_policy()
+        .map_init(
+            || caller.enter(|| Visited::new(grid)),
+            |visited, &(obstruction, pos, dir)| {
+                caller.check()?;
+                let is_loop = detect_loop(&jumps, visited, obstruction, pos, dir);
+                Ok(is_loop.then_some(obstruction))
+            },
+        )
+        .collect::<Result<_, Cancelled>>()?;
+
+    Ok(ret.into_iter().flatten().collect())
 }
 
 // Returns every guard state and if a loop was detected.
-fn get_guard_states(grid: &Grid<GridElem>, start: Point) -> (AHashSet<(Point, Direction)>, bool) {
-    let mut seen = AHashSet::new();
+fn get_guard_states(grid: &Grid<GridElem>, start: Point) -> (DirectionBitGrid, bool) {
+    walk_guard(grid, start, |_, _| ())
+}
 
-    let mut cur_pos = start;
-    let mut cur_dir = Direction::Down; // Up in the problem is towards lower y values.
-    'loop1: loop {
-        for (pos, &value) in grid.iter_line(cur_pos, cur_dir) {
-            if value == GridElem::Wall {
-                cur_dir = cur_dir.rotate_left(); // rotates right
-                continue 'loop1;
-            }
+// Like get_guard_states, but calls on_step with every new guard state.
+fn walk_guard(
+    grid: &Grid<GridElem>,
+    start: Point,
+    mut on_step: impl FnMut(Point, Direction),
+) -> (DirectionBitGrid, bool) {
+    let mut seen = DirectionBitGrid::for_grid(grid);
+    let walls = ObstacleIndex::from_grid(grid, |&c| c == GridElem::Wall);
 
-            if !seen.insert((pos, cur_dir)) {
+    let mut cur_pos = start;
+    let mut cur_dir = Direction::North;
+    loop {
+        // Where the guard turns, or None if it walks off the grid.
+        let stop = walls.slide(cur_pos, cur_dir);
+        for (pos, _) in grid.iter_line(cur_pos, cur_dir) {
+            if !seen.insert(pos, cur_dir) {
                 return (seen, true);
             }
+            on_step(pos, cur_dir);
             cur_pos = pos;
+            if Some(pos) == stop {
+                break;
+            }
         }
 
-        // Hit the end of the grid without hitting a wall.
-        return (seen, false);
+        if stop.is_none() {
+            return (seen, false);
+        }
+        cur_dir = cur_dir.rotate_cw();
     }
 }
 
-fn detect_loop(g: &GridIndex, start: Point, dir: Direction) -> bool {
-    let mut cur_pos = start;
+// Follows the guard from pos facing dir, with an extra obstruction, until
+// it leaves the grid or repeats a turn.
+fn detect_loop(
+    jumps: &JumpTable,
+    visited: &mut Visited,
+    obstruction: Point,
+    pos: Point,
+    dir: Direction,
+) -> bool {
+    visited.clear();
+
+    let mut cur_pos = pos;
     let mut cur_dir = dir;
-
-    let mut seen = AHashSet::new();
     loop {
-        let Some(next_pos) = g.next(cur_pos, cur_dir) else {
+        let Some(next_pos) = jumps.next_turn(cur_pos, cur_dir, obstruction) else {
             return false;
         };
 
-        let next_dir = cur_dir.rotate_left(); // rotates right
+        let next_dir = cur_dir.rotate_cw();
 
-        if !seen.insert((next_pos, next_dir)) {
+        if !visited.insert(jumps.state(next_pos, next_dir)) {
             return true;
         }
 
         cur_pos = next_pos;
-        cur_dir = next_dir; // rotates right
+        cur_dir = next_dir;
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
-enum GridElem {
-    Empty,
-    Wall,
-    GuardStart,
-}
-
-#[derive(Clone, Debug)]
-struct GridIndex {
-    // x -> y values that contain walls.
-    x_index: Vec<BTreeSet<usize>>,
-    // y -> x values that contain walls.
-    y_index: Vec<BTreeSet<usize>>,
+// For every guard state, the position where the guard next turns.
+struct JumpTable {
+    width: usize,
+    // Indexed by JumpTable::state. None if the guard leaves the grid.
+    next: Vec<Option<Point>>,
 }
 
-impl GridIndex {
+impl JumpTable {
     fn new(grid: &Grid<GridElem>) -> Self {
-        let (x_len, y_len) = grid.size();
+        let (width, height) = grid.size();
         let mut ret = Self {
-            x_index: vec![BTreeSet::new(); x_len],
-            y_index: vec![BTreeSet::new(); y_len],
+            width,
+            next: vec![None; width * height * 4],
         };
+        let is_wall = |x, y| grid[Point::new(x, y)] == GridElem::Wall;
+
+        for x in 0..width {
+            let mut stop = None;
+            for y in 0..height {
+                if is_wall(x, y) {
+                    stop = Some(Point::new(x, y + 1));
+                } else {
+                    ret.set(Point::new(x, y), Direction::North, stop);
+                }
+            }
 
-        for (p, v) in grid.iter_items() {
-            if v == &GridElem::Wall {
-                ret.insert(p)
+            let mut stop = None;
+            for y in (0..height).rev() {
+                if is_wall(x, y) {
+                    stop = y.checked_sub(1).map(|y| Point::new(x, y));
+                } else {
+                    ret.set(Point::new(x, y), Direction::South, stop);
+                }
+            }
+        }
+
+        for y in 0..height {
+            let mut stop = None;
+            for x in 0..width {
+                if is_wall(x, y) {
+                    stop = Some(Point::new(x + 1, y));
+                } else {
+                    ret.set(Point::new(x, y), Direction::West, stop);
+                }
+            }
+
+            let mut stop = None;
+            for x in (0..width).rev() {
+                if is_wall(x, y) {
+                    stop = x.checked_sub(1).map(|x| Point::new(x, y));
+                } else {
+                    ret.set(Point::new(x, y), Direction::East, stop);
+                }
             }
         }
 
         ret
     }
 
-    fn insert(&mut self, p: Point) {
-        self.x_index[p.x].insert(p.y);
-        self.y_index[p.y].insert(p.x);
+    fn state(&self, p: Point, d: Direction) -> usize {
+        (p.y * self.width + p.x) * 4 + d as usize
     }
 
-    fn remove(&mut self, p: Point) {
-        self.x_index[p.x].remove(&p.y);
-        self.y_index[p.y].remove(&p.x);
+    fn set(&mut self, p: Point, d: Direction, stop: Option<Point>) {
+        let i = self.state(p, d);
+        self.next[i] = stop;
     }
 
-    // returns location just before next wall.
-    fn next(&self, pos: Point, dir: Direction) -> Option<Point> {
-        let wall_pos = match dir {
-            Direction::Up => self.x_index[pos.x]
-                .range(pos.y + 1..)
-                .next()
-                .map(|&y| Point::new(pos.x, y)),
-            Direction::Down => self.x_index[pos.x]
-                .range(0..pos.y)
-                .rev()
-                .next()
-                .map(|&y| Point::new(pos.x, y)),
-            Direction::Right => self.y_index[pos.y]
-                .range(pos.x + 1..)
-                .next()
-                .map(|&x| Point::new(x, pos.y)),
-            Direction::Left => self.y_index[pos.y]
-                .range(0..pos.x)
-                .rev()
-                .next()
-                .map(|&x| Point::new(x, pos.y)),
-        }?;
+    // Like the table lookup, but stops in front of obstruction if it is
+    // closer than the wall.
+    fn next_turn(&self, pos: Point, dir: Direction, obstruction: Point) -> Option<Point> {
+        let stop = self.next[self.state(pos, dir)];
+        let dist = |p: Point| p.manhattan_distance(pos);
+
+        let ahead = match dir {
+            Direction::North => obstruction.x == pos.x && obstruction.y < pos.y,
+            Direction::South => obstruction.x == pos.x && obstruction.y > pos.y,
+            Direction::West => obstruction.y == pos.y && obstruction.x < pos.x,
+            Direction::East => obstruction.y == pos.y && obstruction.x > pos.x,
+        };
+        if ahead && stop.is_none_or(|s| dist(obstruction) <= dist(s)) {
+            obstruction.next(dir.opposite())
+        } else {
+            stop
+        }
+    }
+}
 
-        wall_pos.next(dir.opposite())
+// A set of guard states that is cleared in O(1) by bumping a generation
+// counter instead of zeroing the array.
+struct Visited {
+    stamps: Vec<u32>,
+    generation: u32,
+}
+
+impl Visited {
+    fn new(grid: &Grid<GridElem>) -> Self {
+        Self {
+            stamps: vec![0; grid.width() * grid.height() * 4],
+            generation: 1,
+        }
+    }
+
+    fn clear(&mut self) {
+        self.generation += 1;
+    }
+
+    // Returns true if state was not in the set.
+    fn insert(&mut self, state: usize) -> bool {
+        let ret = self.stamps[state] != self.generation;
+        self.stamps[state] = self.generation;
+        ret
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum GridElem {
+    Empty,
+    Wall,
+    GuardStart,
+}
+
+impl FromGridChar for GridElem {
+    fn from_char(c: char) -> Option<Self> {
+        match c {
+            '.' => Some(GridElem::Empty),
+            '#' => Some(GridElem::Wall),
+            '^' => Some(GridElem::GuardStart),
+            _ => None,
+        }
+    }
+
+    fn to_char(&self) -> char {
+        match self {
+            GridElem::Empty => '.',
+            GridElem::Wall => '#',
+            GridElem::GuardStart => '^',
+        }
+    }
+
+    fn is_marker(&self) -> bool {
+        *self == GridElem::GuardStart
     }
 }
 
@@ -173,18 +344,8 @@ mod parser {
     use super::*;
     use crate::parser::prelude::*;
 
-    pub fn parse(input: &str) -> IResult<&str, Grid<GridElem>> {
-        let grid_elem = alt((
-            value(GridElem::Empty, char('.')),
-            value(GridElem::Wall, char('#')),
-            value(GridElem::GuardStart, char('^')),
-        ));
-
-        let row = ws_line(many1(grid_elem));
-        let rows = many1(row);
-        let grid = rows.map(Grid::new);
-
-        ws_all_consuming(grid).parse_complete(input)
+    pub fn parse(input: &str) -> IResult<&str, (Grid<GridElem>, Markers<GridElem>)> {
+        ws_all_consuming(grid()).parse_complete(input)
     }
 }
 
@@ -212,4 +373,49 @@ mod tests {
     fn problem2_test() {
         assert_eq!(problem2(EXAMPLE_INPUT).unwrap(), "6")
     }
+
+    #[test]
+    fn loop_obstructions_brute_force_test() {
+        let (grid, markers) = parser::parse(EXAMPLE_INPUT).unwrap().1;
+        let start = markers.find(&GridElem::GuardStart).unwrap();
+
+        let mut expected = Vec::new();
+        for p in grid.iter_points() {
+            if grid[p] != GridElem::Empty {
+                continue;
+            }
+            let mut g = grid.clone();
+            g[p] = GridElem::Wall;
+            if get_guard_states(&g, start).1 {
+                expected.push(p);
+            }
+        }
+
+        let mut got = loop_obstructions(&grid, start).unwrap();
+        got.sort_by_key(|p| (p.y, p.x));
+        assert_eq!(got, expected);
+    }
+
+    #[test]
+    fn render_test() {
+        let out = render(EXAMPLE_INPUT, 2).unwrap();
+        assert_eq!(out.matches('O').count(), 6);
+        assert!(out.contains("6 .#.O^....."), "{}", out);
+    }
+
+    #[test]
+    fn animate_test() {
+        let mut rec = Recorder::new(1);
+        animate(EXAMPLE_INPUT, 1, &mut rec).unwrap();
+        // One frame per turn plus the final state.
+        assert_eq!(rec.frames().len(), 11);
+    }
+
+    #[test]
+    fn ragged_input_test() {
+        let err = problem1("..#.\n.^..\n...\n....").unwrap_err();
+        let msg = format!("{:#}", err);
+        assert!(msg.contains("line 3"), "{}", msg);
+        assert!(msg.contains("has 3 cells, expected 4"), "{}", msg);
+    }
 }
@@ -1,29 +1,38 @@
+use crate::cancel::Cancelled;
+use crate::parallel::{Caller, WithPolicy};
 use crate::solutions::prelude::*;
 
+use rayon::prelude::*;
+
 pub fn problem1(input: &str) -> Result<String, anyhow::Error> {
     let equations = parse!(input);
-
-    let ans = equations
-        .iter()
-        .filter(|(target, terms)| is_valid(*target, terms, false))
-        .map(|(target, _)| target)
-        .sum::<u64>();
-
+    let ans = sum_valid(&equations, false)?;
     Ok(ans.to_string())
 }
 
 pub fn problem2(input: &str) -> Result<String, anyhow::Error> {
     let equations = parse!(input);
-
-    let ans = equations
-        .iter()
-        .filter(|(target, terms)| is_valid(*target, terms, true))
-        .map(|(target, _)| target)
-        .sum::<u64>();
-
+    let ans = sum_valid(&equations, true)?;
     Ok(ans.to_string())
 }
 
+// Sums the targets of the equations that can be made true.
+fn sum_valid(equations: &[(u64, Vec<u64>)], third_op: bool) -> Result<u64, Cancelled> {
+    let caller = Caller::capture();
+    equations
+        .par_iter()
+        .with_policy()
+        .map(|(target, terms)| {
+            caller.check()?;
+            Ok(if is_valid(*target, terms, third_op) {
+                *target
+            } else {
+                0
+            })
+        })
+        .sum()
+}
+
 fn is_valid(target: u64, terms: &[u64], third_op: bool) -> bool {
     fn rec(current: u64, target: u64, terms: &[u64], third_op: bool) -> bool {
         if terms.is_empty() {
@@ -135,7 +135,7 @@ impl FreeList {
         let queue = self.free_list[length as usize..]
             .iter_mut()
             .filter_map(|q| {
-                let r = q.front()?.clone();
+                let r = *q.front()?;
                 Some((q, r))
             })
             .min_by_key(|(_, r)| *r)
@@ -76,11 +76,7 @@ mod parser {
     use crate::parser::prelude::*;
 
     pub fn parse(input: &str) -> IResult<&str, Grid<u8>> {
-        let elem = one_of("0123456789").map(|c| c.to_digit(10).unwrap() as u8);
-        let row = ws_line(many1(elem));
-        let rows = many1(row);
-        let grid = rows.map(Grid::from);
-        ws_all_consuming(grid).parse_complete(input)
+        ws_all_consuming(grid().map(|(g, _)| g)).parse_complete(input)
     }
 }
 
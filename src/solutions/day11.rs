@@ -46,7 +46,7 @@ fn count_step(i: &AHashMap<u64, u64>, o: &mut AHashMap<u64, u64>) {
 
 fn split_num_if_even(n: u64) -> Option<(u64, u64)> {
     let num_digits = n.checked_ilog10().unwrap_or(0) + 1;
-    if num_digits % 2 == 0 {
+    if num_digits.is_multiple_of(2) {
         let factor = 10u64.pow(num_digits / 2);
         Some((n / factor, n % factor))
     } else {
@@ -1,73 +1,112 @@
-use std::collections::BinaryHeap;
-
 use ahash::AHashSet;
 
-use crate::grid::{Direction, Grid, Point};
+use crate::grid::bits::DirectionBitGrid;
+use crate::grid::{Direction, FromGridChar, Grid, Markers, Point};
+use crate::search::{self, DenseMap, NodeMap, SearchResult};
 use crate::solutions::prelude::*;
-use crate::utils::RevHeapElem;
 
 pub fn problem1(input: &str) -> Result<String, anyhow::Error> {
-    let grid = parse!(input);
+    let (grid, markers) = parse!(input);
 
-    let maze = Maze::try_from(grid)?;
+    let maze = Maze::new(grid, &markers)?;
 
     let ans = distance(&maze.grid, maze.start, maze.end)?;
 
     Ok(ans.to_string())
 }
 
-pub fn problem2(_input: &str) -> Result<String, anyhow::Error> {
-    bail!("not yet implemented")
-}
+pub fn problem2(input: &str) -> Result<String, anyhow::Error> {
+    let (grid, markers) = parse!(input);
 
-fn distance(grid: &Grid<GridElem>, start: Point, end: Point) -> Result<u64, anyhow::Error> {
-    let mut frontier = BinaryHeap::new();
-    frontier.push(RevHeapElem {
-        key: 0,
-        value: (start, Direction::Right),
-    });
-    let mut visited = AHashSet::new();
-
-    while let Some(elem) = frontier.pop() {
-        let cost = elem.key;
-        let (cur_pos, cur_dir) = elem.value;
-
-        if cur_pos == end {
-            return Ok(cost);
-        };
+    let maze = Maze::new(grid, &markers)?;
 
-        if !visited.insert(elem.value) {
-            continue;
-        };
-
-        let candidate_dir = [cur_dir, cur_dir.rotate_left(), cur_dir.rotate_right()];
+    let res = search_maze(&maze.grid, maze.start, maze.end);
+    if res.goals().is_empty() {
+        bail!("end unreachable");
+    }
+    let tiles: AHashSet<Point> = res
+        .optimal_states(res.goals())
+        .into_iter()
+        .map(|(pos, _)| pos)
+        .collect();
 
-        for dir in candidate_dir {
-            let Some(next_pos) = cur_pos.next(dir) else {
-                continue;
-            };
+    Ok(tiles.len().to_string())
+}
 
-            match grid.get(next_pos) {
-                None | Some(&GridElem::Wall) => {
-                    continue;
-                }
-                _ => (),
-            };
+// Draws one best path for problem 1 and every tile on any best path for
+// problem 2.
+pub fn render(input: &str, problem: usize) -> Result<String, anyhow::Error> {
+    let (grid, markers) = parse!(input);
+    let maze = Maze::new(grid, &markers)?;
 
-            if visited.contains(&(next_pos, dir)) {
-                continue;
-            };
+    let res = search_maze(&maze.grid, maze.start, maze.end);
+    let tiles: Vec<Point> = match problem {
+        1 => res.goal_path().context("end unreachable")?,
+        _ => res.optimal_states(res.goals()).into_iter().collect(),
+    }
+    .into_iter()
+    .map(|(pos, _)| pos)
+    .collect();
+
+    Ok(maze
+        .grid
+        .render()
+        .overlay('O', tiles)
+        .overlay('S', [maze.start])
+        .overlay('E', [maze.end])
+        .rulers(true)
+        .to_string())
+}
 
-            let new_cost = cost + if cur_dir == dir { 1 } else { 1001 };
+// Part 1 only needs the distance, so it skips recording predecessors.
+fn distance(grid: &Grid<GridElem>, start: Point, end: Point) -> Result<u64, anyhow::Error> {
+    search::dijkstra_distance(
+        DirectionBitGrid::for_grid(grid),
+        [(start, Direction::East)],
+        |&s| successors(grid, s),
+        |&(pos, _)| pos == end,
+    )
+    .context("end unreachable")
+}
 
-            frontier.push(RevHeapElem {
-                key: new_cost,
-                value: (next_pos, dir),
-            });
-        }
-    }
+// Searches from start, facing east, until every best way of reaching end
+// is found.
+fn search_maze(
+    grid: &Grid<GridElem>,
+    start: Point,
+    end: Point,
+) -> SearchResult<(Point, Direction), impl NodeMap<(Point, Direction)>> {
+    // States are numbered densely so the search doesn't need to hash them.
+    let width = grid.width();
+    let nodes = DenseMap::new(
+        grid.width() * grid.height() * 4,
+        move |&(p, d): &(Point, Direction)| (p.y * width + p.x) * 4 + d as usize,
+    );
+    search::dijkstra_in(
+        nodes,
+        [(start, Direction::East)],
+        |&s| successors(grid, s),
+        |&(pos, _)| pos == end,
+    )
+}
 
-    bail!("end unreachable");
+// Moving on costs 1 and turning first costs another 1000.
+fn successors(
+    grid: &Grid<GridElem>,
+    (pos, dir): (Point, Direction),
+) -> impl Iterator<Item = ((Point, Direction), u64)> {
+    [dir, dir.rotate_ccw(), dir.rotate_cw()]
+        .into_iter()
+        .filter_map(move |next_dir| {
+            let next_pos = pos.next(next_dir)?;
+            match grid.get(next_pos) {
+                None | Some(&GridElem::Wall) => None,
+                _ => {
+                    let cost = if next_dir == dir { 1 } else { 1001 };
+                    Some(((next_pos, next_dir), cost))
+                }
+            }
+        })
 }
 
 struct Maze {
@@ -76,15 +115,13 @@ struct Maze {
     end: Point,
 }
 
-impl TryFrom<Grid<GridElem>> for Maze {
-    type Error = anyhow::Error;
-
-    fn try_from(grid: Grid<GridElem>) -> Result<Self, Self::Error> {
-        let Some((start, _)) = grid.iter_items().find(|(_, e)| **e == GridElem::Start) else {
+impl Maze {
+    fn new(grid: Grid<GridElem>, markers: &Markers<GridElem>) -> Result<Self, anyhow::Error> {
+        let Some(start) = markers.find(&GridElem::Start) else {
             bail!("no start found")
         };
 
-        let Some((end, _)) = grid.iter_items().find(|(_, e)| **e == GridElem::End) else {
+        let Some(end) = markers.find(&GridElem::End) else {
             bail!("no end found")
         };
 
@@ -100,23 +137,37 @@ enum GridElem {
     End,
 }
 
-mod parser {
-    use super::*;
-    use crate::{grid::Grid, parser::prelude::*};
+impl FromGridChar for GridElem {
+    fn from_char(c: char) -> Option<Self> {
+        match c {
+            '.' => Some(GridElem::Empty),
+            '#' => Some(GridElem::Wall),
+            'S' => Some(GridElem::Start),
+            'E' => Some(GridElem::End),
+            _ => None,
+        }
+    }
+
+    fn to_char(&self) -> char {
+        match self {
+            GridElem::Empty => '.',
+            GridElem::Wall => '#',
+            GridElem::Start => 'S',
+            GridElem::End => 'E',
+        }
+    }
 
-    pub fn parse(input: &str) -> IResult<&str, Grid<GridElem>> {
-        let grid_elem = alt((
-            value(GridElem::Empty, char('.')),
-            value(GridElem::Wall, char('#')),
-            value(GridElem::Start, char('S')),
-            value(GridElem::End, char('E')),
-        ));
+    fn is_marker(&self) -> bool {
+        matches!(self, GridElem::Start | GridElem::End)
+    }
+}
 
-        let row = ws_line(many1(grid_elem));
-        let rows = many1(row);
-        let grid = rows.map(Grid::new);
+mod parser {
+    use super::*;
+    use crate::parser::prelude::*;
 
-        ws_all_consuming(grid).parse_complete(input)
+    pub fn parse(input: &str) -> IResult<&str, (Grid<GridElem>, Markers<GridElem>)> {
+        ws_all_consuming(grid()).parse_complete(input)
     }
 }
 
@@ -145,8 +196,19 @@ mod tests {
         assert_eq!(problem1(EXAMPLE_INPUT).unwrap(), "7036")
     }
 
+    #[test]
+    fn render_test() {
+        let out = render(EXAMPLE_INPUT, 2).unwrap();
+        // The 45 best path tiles include S and E.
+        assert_eq!(out.matches('O').count(), 43);
+        assert!(out.contains(" 1 #.......#....E#"), "{}", out);
+
+        let out = render(EXAMPLE_INPUT, 1).unwrap();
+        assert_eq!(out.matches('O').count(), 35);
+    }
+
     #[test]
     fn problem2_test() {
-        //assert_eq!(problem2(EXAMPLE_INPUT).unwrap(), "")
+        assert_eq!(problem2(EXAMPLE_INPUT).unwrap(), "45")
     }
 }
@@ -1,62 +1,183 @@
-use ahash::{AHashMap, AHashSet};
-use arrayvec::ArrayVec;
+use std::{cmp::Reverse, fmt};
 
+use rayon::prelude::*;
+
+use crate::parallel::{Caller, WithPolicy};
 use crate::solutions::prelude::*;
 
+// The number of buyers whose secrets are generated together.
+const LANES: usize = 8;
+const MODULUS: u32 = 1 << 24;
+// The number of secrets of each buyer, including the seed.
+const SECRETS: usize = 2000;
+
 pub fn problem1(input: &str) -> Result<String, anyhow::Error> {
-    let seeds = parse!(input);
-    let ans: u64 = seeds.iter().map(|&seed| nth_secret(seed, 2000)).sum();
+    let seeds = parse_seeds(input)?;
+    let ans: u64 = seeds
+        .par_chunks(LANES)
+        .with_policy()
+        .map(|chunk| {
+            let mut lanes = Lanes::new(chunk);
+            for _ in 0..SECRETS {
+                lanes.step();
+            }
+            lanes.secrets().iter().map(|&s| s as u64).sum::<u64>()
+        })
+        .sum();
     Ok(ans.to_string())
 }
 
 pub fn problem2(input: &str) -> Result<String, anyhow::Error> {
-    let seeds = parse!(input);
+    let seeds = parse_seeds(input)?;
+    let (_, bananas) = best_pattern(&seeds).context("no buyers")?;
+    Ok(bananas.to_string())
+}
 
-    let mut pattern_map = AHashMap::new();
+// Reports the change sequence that wins problem 2.
+pub fn render(input: &str, problem: usize) -> Result<String, anyhow::Error> {
+    if problem != 2 {
+        bail!("only problem 2 can be rendered");
+    }
 
-    for seed in seeds {
-        let mut price_diff = iter_prices_diff(seed, 2000);
+    let seeds = parse_seeds(input)?;
+    let (pattern, bananas) = best_pattern(&seeds).context("no buyers")?;
+    Ok(format!("Best sequence: {} ({} bananas)", pattern, bananas))
+}
 
-        let mut pattern = (&mut price_diff)
-            .take(3)
-            .fold(Pattern::default(), |acc, (d, _)| acc.push(d).unwrap());
+fn parse_seeds(input: &str) -> Result<Vec<u32>, anyhow::Error> {
+    parse!(input)
+        .into_iter()
+        .map(|seed| match u32::try_from(seed) {
+            Ok(s) if s < MODULUS => Ok(s),
+            _ => bail!("seed out of range: {}", seed),
+        })
+        .collect()
+}
 
-        let mut seen = AHashSet::new();
-        for (d, p) in price_diff {
-            pattern = pattern.push(d).unwrap();
-            if seen.insert(pattern) {
-                *pattern_map.entry(pattern).or_insert(0) += p as u64;
-            }
-        }
+// The pattern that earns the most bananas and how many. Ties go to the
+// pattern with the lowest index.
+fn best_pattern(seeds: &[u32]) -> Option<(Pattern, u32)> {
+    // Each worker totals the prices of its buyers, then the totals are
+    // merged.
+    let caller = Caller::capture();
+    let totals = seeds
+        .par_chunks(LANES)
+        .with_policy()
+        .fold(
+            || caller.enter(Totals::new),
+            |mut totals, chunk| {
+                caller.enter(|| totals.add_chunk(chunk));
+                totals
+            },
+        )
+        .reduce(
+            || caller.enter(Totals::new),
+            |a, b| caller.enter(|| a.merge(b)),
+        );
+
+    if seeds.is_empty() {
+        return None;
     }
 
-    let ans = pattern_map.values().max().unwrap();
-    Ok(ans.to_string())
+    totals
+        .bananas
+        .iter()
+        .enumerate()
+        .max_by_key(|&(i, &b)| (b, Reverse(i)))
+        .map(|(i, &b)| (Pattern(i as u32), b))
 }
 
-// Takes the first n prices and provides a Vec of n-1 (diff, price) tuples
-fn iter_prices_diff(seed: u64, n: usize) -> impl Iterator<Item = (i8, u8)> {
-    let secrets = std::iter::successors(Some(seed), |&x| Some(next_secret(x))).take(n);
-    let mut prices = secrets.map(|x| (x % 10) as u8);
+// Bananas earned per pattern, summed over buyers.
+struct Totals {
+    // Indexed by Pattern.
+    bananas: Vec<u32>,
+    // The last buyer that saw each pattern, so only the first occurrence
+    // per buyer counts.
+    seen: Vec<u32>,
+    buyer: u32,
+}
 
-    let initial_value = match next_chunk(&mut prices) {
-        Ok([a, b]) => Some((b as i8 - a as i8, b)),
-        Err(_) => None,
-    };
+impl Totals {
+    fn new() -> Self {
+        Self {
+            bananas: vec![0; Pattern::COUNT],
+            seen: vec![0; Pattern::COUNT],
+            buyer: 0,
+        }
+    }
 
-    std::iter::successors(initial_value, move |&(_, prev)| {
-        let p = prices.next()?;
-        Some((p as i8 - prev as i8, p))
-    })
+    // Adds the buyers with the seeds in chunk, at most LANES of them.
+    fn add_chunk(&mut self, chunk: &[u32]) {
+        let mut lanes = Lanes::new(chunk);
+        let mut prices = Vec::with_capacity(SECRETS);
+        for _ in 0..SECRETS {
+            prices.push(lanes.secrets.map(|s| (s % 10) as u8));
+            lanes.step();
+        }
+
+        for lane in 0..chunk.len() {
+            self.add_buyer(prices.iter().map(|p| p[lane]));
+        }
+    }
+
+    fn add_buyer(&mut self, prices: impl Iterator<Item = u8>) {
+        self.buyer += 1;
+
+        let mut pattern = Pattern::default();
+        let mut prev = None;
+        for (i, p) in prices.enumerate() {
+            if let Some(prev) = prev {
+                pattern = pattern.push(p as i8 - prev as i8).unwrap();
+            }
+            prev = Some(p);
+
+            // The first pattern is complete after four changes.
+            let idx = pattern.0 as usize;
+            if i >= 4 && self.seen[idx] != self.buyer {
+                self.seen[idx] = self.buyer;
+                self.bananas[idx] += p as u32;
+            }
+        }
+    }
+
+    fn merge(mut self, other: Totals) -> Self {
+        for (a, b) in self.bananas.iter_mut().zip(&other.bananas) {
+            *a += b;
+        }
+        self
+    }
 }
 
-fn nth_secret(seed: u64, n: usize) -> u64 {
-    let mut res = seed;
-    for _ in 0..n {
-        res = next_secret(res);
+// The secrets of up to LANES buyers. A full set of lanes is advanced with
+// next_secret_lanes, a partial one with next_secret.
+struct Lanes {
+    secrets: [u32; LANES],
+    len: usize,
+}
+
+impl Lanes {
+    fn new(seeds: &[u32]) -> Self {
+        let mut secrets = [0; LANES];
+        secrets[..seeds.len()].copy_from_slice(seeds);
+        Self {
+            secrets,
+            len: seeds.len(),
+        }
+    }
+
+    fn secrets(&self) -> &[u32] {
+        &self.secrets[..self.len]
     }
 
-    res
+    fn step(&mut self) {
+        if self.len == LANES {
+            self.secrets = next_secret_lanes(self.secrets);
+        } else {
+            for s in &mut self.secrets[..self.len] {
+                *s = next_secret(*s as u64) as u32;
+            }
+        }
+    }
 }
 
 fn next_secret(prev: u64) -> u64 {
@@ -73,27 +194,59 @@ fn next_secret(prev: u64) -> u64 {
     s
 }
 
-fn next_chunk<const N: usize, T>(iter: impl Iterator<Item = T>) -> Result<[T; N], ArrayVec<T, N>> {
-    let mut ret = ArrayVec::new();
-    ret.extend(iter.take(N));
-    ret.into_inner()
+// next_secret for every lane. Each step is a separate loop over the array so
+// the compiler turns it into vector instructions. Secrets are below 2^24, so
+// bits shifted out of the u32 never survive the prune.
+fn next_secret_lanes(mut s: [u32; LANES]) -> [u32; LANES] {
+    const MASK: u32 = MODULUS - 1;
+
+    for x in &mut s {
+        *x = (*x ^ (*x << 6)) & MASK;
+    }
+    for x in &mut s {
+        *x ^= *x >> 5;
+    }
+    for x in &mut s {
+        *x = (*x ^ (*x << 11)) & MASK;
+    }
+
+    s
 }
 
-// A pattern is logically an array of 4 ints in the range [-9, 9]. The int is
-// shifted up by 9 and then bit packed where every 6 bits is a new number.
+// A pattern is logically an array of 4 ints in the range [-9, 9]. The ints
+// are shifted up by 9 and stored as the digits of a base 19 number, so every
+// pattern is an index below 19^4.
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
 struct Pattern(u32);
 
 impl Pattern {
+    const COUNT: usize = 19usize.pow(4);
+
     // Removes the first int and adds the new int on the end
     fn push(&self, n: i8) -> Option<Self> {
-        if !(n >= -9 && n <= 9) {
+        if !(-9..=9).contains(&n) {
             return None;
         }
 
         let shifted_n = (n + 9) as u32;
-        let ret = ((self.0 << 6) + shifted_n) & 0xFFFFFF;
-        Some(Self(ret))
+        Some(Self((self.0 % 19u32.pow(3)) * 19 + shifted_n))
+    }
+
+    fn changes(&self) -> [i8; 4] {
+        let mut ret = [0; 4];
+        let mut n = self.0;
+        for c in ret.iter_mut().rev() {
+            *c = (n % 19) as i8 - 9;
+            n /= 19;
+        }
+        ret
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [a, b, c, d] = self.changes();
+        write!(f, "{},{},{},{}", a, b, c, d)
     }
 }
 
@@ -110,6 +263,15 @@ mod parser {
 mod tests {
     use super::*;
 
+    fn nth_secret(seed: u64, n: usize) -> u64 {
+        let mut res = seed;
+        for _ in 0..n {
+            res = next_secret(res);
+        }
+
+        res
+    }
+
     #[test]
     fn next_secret_test() {
         assert_eq!(next_secret(123), 15887950);
@@ -120,6 +282,18 @@ mod tests {
         assert_eq!(nth_secret(1, 2000), 8685429);
     }
 
+    #[test]
+    fn next_secret_lanes_test() {
+        let seeds = [123, 1, 10, 100, 2024, 0, 16777215, 8685429];
+        let mut lanes = seeds;
+        let mut scalar = seeds.map(|s| s as u64);
+        for _ in 0..100 {
+            lanes = next_secret_lanes(lanes);
+            scalar = scalar.map(next_secret);
+            assert_eq!(lanes.map(|s| s as u64), scalar);
+        }
+    }
+
     #[test]
     fn problem1_test() {
         const EXAMPLE_INPUT: &str = "1
@@ -137,6 +311,10 @@ mod tests {
         3
         2024";
 
-        assert_eq!(problem2(EXAMPLE_INPUT).unwrap(), "23")
+        assert_eq!(problem2(EXAMPLE_INPUT).unwrap(), "23");
+        assert_eq!(
+            render(EXAMPLE_INPUT, 2).unwrap(),
+            "Best sequence: -2,1,-1,3 (23 bananas)"
+        );
     }
 }
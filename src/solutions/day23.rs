@@ -1,7 +1,8 @@
-use std::collections::BTreeSet;
+use std::{collections::BTreeSet, fmt};
 
 use ahash::{AHashMap, AHashSet};
 
+use crate::cancel::{self, Cancelled};
 use crate::solutions::prelude::*;
 
 pub fn problem1(input: &str) -> Result<String, anyhow::Error> {
@@ -32,65 +33,257 @@ pub fn problem1(input: &str) -> Result<String, anyhow::Error> {
 
 pub fn problem2(input: &str) -> Result<String, anyhow::Error> {
     let data = parse!(input);
-    let nodes = {
-        let mut nodes: AHashMap<NodeId, BTreeSet<NodeId>> = AHashMap::new();
-        for (a, b) in data {
-            nodes.entry(a).or_default().insert(b);
-            nodes.entry(b).or_default().insert(a);
-        }
-        nodes
+    let graph = Graph::new(&data);
+
+    let cliques = graph.maximum_cliques()?;
+    let Some(clique) = cliques.first() else {
+        bail!("no nodes");
     };
 
-    let ubs: Vec<_> = nodes.keys().map(|n| (n, upper_bound(n, &nodes))).collect();
-    let max = ubs.iter().max_by_key(|(_, ub)| *ub).unwrap();
-    let mut ret_nodes: Vec<_> = ubs
-        .iter()
-        .filter(|(_, ub)| *ub == max.1)
-        .map(|(n, _)| n)
-        .copied()
-        .collect();
-    ret_nodes.sort();
-
-    let ans = ret_nodes
-        .iter()
-        .map(|n| format!("{:?}", n))
-        .collect::<Vec<_>>()
-        .join(",");
-
-    Ok(ans)
+    Ok(graph.password(clique))
 }
 
-fn upper_bound(node: &NodeId, nodes: &AHashMap<NodeId, BTreeSet<NodeId>>) -> u64 {
-    let neighbors = nodes.get(node).unwrap();
-    let mut neigh_in_common: Vec<_> = neighbors
-        .iter()
-        .map(|n| nodes.get(n).unwrap().intersection(neighbors).count() as u64)
-        .collect();
-    neigh_in_common.sort_unstable();
+// Lists every maximum clique for problem 2, in case there is a tie.
+pub fn render(input: &str, problem: usize) -> Result<String, anyhow::Error> {
+    if problem != 2 {
+        bail!("only problem 2 can be rendered");
+    }
+
+    let data = parse!(input);
+    let graph = Graph::new(&data);
+    let cliques = graph.maximum_cliques()?;
+
+    let mut ret = format!("{} maximum cliques:", cliques.len());
+    for c in &cliques {
+        ret += "\n";
+        ret += &graph.password(c);
+    }
+    Ok(ret)
+}
+
+// The network with nodes numbered in NodeId order.
+struct Graph {
+    names: Vec<NodeId>,
+    adjacent: Vec<NodeSet>,
+}
+
+impl Graph {
+    fn new(edges: &[(NodeId, NodeId)]) -> Self {
+        let names: Vec<_> = edges
+            .iter()
+            .flat_map(|&(a, b)| [a, b])
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        let index: AHashMap<_, _> = names.iter().enumerate().map(|(i, &n)| (n, i)).collect();
+
+        let mut adjacent = vec![NodeSet::new(names.len()); names.len()];
+        for (a, b) in edges {
+            let (a, b) = (index[a], index[b]);
+            // Self loops would make a node its own neighbour.
+            if a != b {
+                adjacent[a].insert(b);
+                adjacent[b].insert(a);
+            }
+        }
+
+        Self { names, adjacent }
+    }
+
+    // Every clique of the largest size, each sorted and in sorted order.
+    fn maximum_cliques(&self) -> Result<Vec<Vec<usize>>, anyhow::Error> {
+        let n = self.names.len();
+        let mut cliques = Vec::new();
+
+        // Starting from each node in degeneracy order, only the neighbours
+        // later in the order are candidates. That bounds the candidate sets
+        // by the graph's degeneracy rather than its maximum degree.
+        let mut later = NodeSet::full(n);
+        for v in self.degeneracy_order() {
+            later.remove(v);
+            let candidates = self.adjacent[v].intersection(&later);
+            let excluded = self.adjacent[v].difference(&later);
+            self.bron_kerbosch(&mut vec![v], candidates, excluded, &mut cliques)?;
+        }
+
+        for c in &mut cliques {
+            c.sort_unstable();
+            if !self.is_clique(c) {
+                bail!("found a set that is not a clique: {}", self.password(c));
+            }
+        }
+        cliques.sort();
+        Ok(cliques)
+    }
+
+    // Adds every maximal clique that extends clique with nodes of
+    // candidates, and is at least as large as those in cliques, to cliques.
+    // Nodes in excluded are already covered by other branches.
+    fn bron_kerbosch(
+        &self,
+        clique: &mut Vec<usize>,
+        mut candidates: NodeSet,
+        mut excluded: NodeSet,
+        cliques: &mut Vec<Vec<usize>>,
+    ) -> Result<(), Cancelled> {
+        cancel::check()?;
+
+        let best = cliques.first().map_or(0, |c| c.len());
+        if clique.len() + candidates.len() < best {
+            return Ok(());
+        }
+
+        if candidates.is_empty() {
+            if excluded.is_empty() {
+                if clique.len() > best {
+                    cliques.clear();
+                }
+                cliques.push(clique.clone());
+            }
+            return Ok(());
+        }
+
+        // Every maximal clique contains the pivot or one of its
+        // non-neighbours, so only those need to be tried.
+        let pivot = candidates
+            .iter()
+            .chain(excluded.iter())
+            .max_by_key(|&u| self.adjacent[u].intersection(&candidates).len())
+            .unwrap();
+
+        for v in candidates.difference(&self.adjacent[pivot]).iter() {
+            clique.push(v);
+            self.bron_kerbosch(
+                clique,
+                candidates.intersection(&self.adjacent[v]),
+                excluded.intersection(&self.adjacent[v]),
+                cliques,
+            )?;
+            clique.pop();
+
+            candidates.remove(v);
+            excluded.insert(v);
+        }
+
+        Ok(())
+    }
+
+    // Repeatedly removes a node of minimum degree from what is left.
+    fn degeneracy_order(&self) -> Vec<usize> {
+        let n = self.names.len();
+        let mut degree: Vec<_> = self.adjacent.iter().map(|a| a.len()).collect();
+        let mut removed = vec![false; n];
+        let mut ret = Vec::with_capacity(n);
+
+        for _ in 0..n {
+            let v = (0..n)
+                .filter(|&v| !removed[v])
+                .min_by_key(|&v| degree[v])
+                .unwrap();
+            removed[v] = true;
+            ret.push(v);
+            for u in self.adjacent[v].iter() {
+                degree[u] -= 1;
+            }
+        }
+
+        ret
+    }
 
-    n_gt_n(&neigh_in_common) + 2
+    fn is_clique(&self, nodes: &[usize]) -> bool {
+        nodes
+            .iter()
+            .enumerate()
+            .all(|(i, &a)| nodes[i + 1..].iter().all(|&b| self.adjacent[a].contains(b)))
+    }
+
+    // The sorted names of nodes joined by commas.
+    fn password(&self, nodes: &[usize]) -> String {
+        let mut names: Vec<_> = nodes.iter().map(|&i| self.names[i]).collect();
+        names.sort();
+        names
+            .iter()
+            .map(|n| n.to_string())
+            .collect::<Vec<_>>()
+            .join(",")
+    }
 }
 
-// Takes a sorted list and finds the highest n such that there are n numbers
-// greater than n.
-fn n_gt_n(values: &[u64]) -> u64 {
-    values
-        .iter()
-        .rev()
-        .enumerate()
-        .take_while(|&(i, &x)| (i + 1) as u64 <= x)
-        .count() as u64
+// A set of node indexes below a fixed size, one bit per node.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct NodeSet(Vec<u64>);
+
+impl NodeSet {
+    fn new(size: usize) -> Self {
+        Self(vec![0; size.div_ceil(64)])
+    }
+
+    fn full(size: usize) -> Self {
+        let mut ret = Self::new(size);
+        for i in 0..size {
+            ret.insert(i);
+        }
+        ret
+    }
+
+    fn insert(&mut self, i: usize) {
+        self.0[i / 64] |= 1 << (i % 64);
+    }
+
+    fn remove(&mut self, i: usize) {
+        self.0[i / 64] &= !(1 << (i % 64));
+    }
+
+    fn contains(&self, i: usize) -> bool {
+        self.0[i / 64] & (1 << (i % 64)) != 0
+    }
+
+    fn len(&self) -> usize {
+        self.0.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.iter().all(|&w| w == 0)
+    }
+
+    fn intersection(&self, other: &NodeSet) -> NodeSet {
+        NodeSet(self.0.iter().zip(&other.0).map(|(a, b)| a & b).collect())
+    }
+
+    fn difference(&self, other: &NodeSet) -> NodeSet {
+        NodeSet(self.0.iter().zip(&other.0).map(|(a, b)| a & !b).collect())
+    }
+
+    fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.0.iter().enumerate().flat_map(|(wi, &w)| {
+            let mut w = w;
+            std::iter::from_fn(move || {
+                if w == 0 {
+                    return None;
+                }
+                let i = wi * 64 + w.trailing_zeros() as usize;
+                w &= w - 1;
+                Some(i)
+            })
+        })
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 struct NodeId([u8; 2]);
 
-impl std::fmt::Debug for NodeId {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl fmt::Display for NodeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", String::from_utf8_lossy(&self.0))
     }
 }
 
+impl fmt::Debug for NodeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
 mod parser {
     use super::*;
     use crate::parser::prelude::*;
@@ -156,4 +349,22 @@ mod tests {
     fn problem2_test() {
         assert_eq!(problem2(EXAMPLE_INPUT).unwrap(), "co,de,ka,ta")
     }
+
+    #[test]
+    fn maximum_cliques_test() {
+        // Two triangles sharing node bb, plus a pendant node.
+        let edges = parser::parse("aa-bb\nbb-cc\ncc-aa\nbb-dd\ndd-ee\nee-bb\nee-ff").unwrap();
+        let g = Graph::new(&edges.1);
+        let cliques = g.maximum_cliques().unwrap();
+        let names: Vec<_> = cliques.iter().map(|c| g.password(c)).collect();
+        assert_eq!(names, vec!["aa,bb,cc", "bb,dd,ee"]);
+    }
+
+    #[test]
+    fn render_test() {
+        assert_eq!(
+            render(EXAMPLE_INPUT, 2).unwrap(),
+            "1 maximum cliques:\nco,de,ka,ta"
+        );
+    }
 }